@@ -8,6 +8,10 @@ use std::{
 use clap::{App, Arg};
 use easy_fs::{BlockDevice, EasyFileSystem, BLOCK_SIZE};
 
+// 需要与内核中的 SWAP_START_BLOCK 和 SWAP_BLOCKS 保持一致
+const FS_BLOCKS: usize = 16 * 2048;
+const SWAP_BLOCKS: usize = 16 * 2048;
+
 struct BlockFile(Mutex<File>);
 
 impl BlockDevice for BlockFile {
@@ -63,12 +67,14 @@ fn easy_fs_pack() -> std::io::Result<()> {
             .write(true)
             .create(true)
             .open(Path::new(target_path).join("fs.img").to_str().unwrap())?;
-        f.set_len(16 * 2048 * 512).unwrap();
+        // 文件系统之后是内核使用的交换区
+        f.set_len(((FS_BLOCKS + SWAP_BLOCKS) * BLOCK_SIZE) as u64)
+            .unwrap();
         f
     })));
 
     // 16MiB, at most 4095 files
-    let efs = EasyFileSystem::create(block_file, 16 * 2048, 1);
+    let efs = EasyFileSystem::create(block_file, FS_BLOCKS as u32, 1);
     let root_inode = Arc::new(EasyFileSystem::root_inode(efs.clone()));
     let apps: Vec<_> = read_dir(src_path)
         .unwrap()
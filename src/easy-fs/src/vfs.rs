@@ -2,7 +2,7 @@ use alloc::{string::String, sync::Arc, vec::Vec};
 use spin::{Mutex, MutexGuard};
 
 use crate::{
-    block_cache::{block_cache_sync_all, get_block_cache},
+    block_cache::{block_cache_sync_all, get_block_cache, BLOCK_SIZE},
     block_dev::BlockDevice,
     efs::EasyFileSystem,
     layout::{DirEntry, DiskInode, DiskInodeType, DIR_ENTRY_SIZE},
@@ -30,6 +30,11 @@ impl Inode {
         }
     }
 
+    // inode 在磁盘上的位置，同一个文件每次打开得到的 Inode 都有相同的 id
+    pub fn id(&self) -> usize {
+        self.block_id * BLOCK_SIZE + self.block_offset
+    }
+
     // 读取 inode 对应的 disk inode
     fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskInode) -> V) -> V {
         get_block_cache(self.block_id, self.block_device.clone())
@@ -161,6 +166,12 @@ impl Inode {
         });
     }
 
+    // 文件的大小（字节）
+    pub fn size(&self) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.size as usize)
+    }
+
     // 从 data block 中读取数据
     pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
         let _fs = self.fs.lock();
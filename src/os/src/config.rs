@@ -1,8 +1,9 @@
 pub const USER_STACK_SIZE: usize = 4096 * 2;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
-pub const KERNEL_HEAP_SIZE: usize = 0x30_0000;
-
-pub const MEMORY_END: usize = 0x80800000;
+// 内核堆初始时的大小，不够时每次从页框分配器中申请至少 KERNEL_HEAP_GROW_SIZE 字节，
+// 两者都必须是 2 的幂
+pub const KERNEL_HEAP_SIZE: usize = 0x10_0000;
+pub const KERNEL_HEAP_GROW_SIZE: usize = 0x4_0000;
 
 // address space
 pub const PAGE_SIZE: usize = 0x1000;
@@ -12,10 +13,40 @@ pub const PAGE_SIZE_BITS: usize = 0xc;
 // Ref: https://rcore-os.github.io/rCore-Tutorial-Book-v3/chapter4/5kernel-app-spaces.html#id6
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
+// 用户态可执行的 sigreturn 跳板，信号处理函数返回时跳转到这里
+pub const SIGRETURN_TRAMPOLINE: usize = TRAP_CONTEXT - PAGE_SIZE;
+
+// 用户地址空间中留给 mmap 的区域，位于用户程序和用户栈之上，
+// 远离 SV39 用户地址空间的上限 (1 << 38)
+pub const MMAP_BASE: usize = 0x10_0000_0000;
+pub const MMAP_END: usize = 0x20_0000_0000;
+
+// 用户栈的栈顶，位于 mmap 区域之下，并且与 mmap 区域之间隔着一个 guard page。
+// 用户堆从程序的最后一个段之后开始向上增长，与用户栈之间留有足够的空间。
+// 开启了地址空间布局随机化时，这里是用户栈的栈顶可以取到的最高地址。
+pub const USER_STACK_TOP: usize = MMAP_BASE - PAGE_SIZE;
+// 用户栈初始时的大小为 USER_STACK_SIZE，访问栈底之下的地址时自动向下增长，
+// 最多增长到 USER_STACK_LIMIT
+pub const USER_STACK_LIMIT: usize = 0x80_0000;
+
+// 位置无关的可执行文件 (ET_DYN) 被加载到的基址，远离 ET_EXEC 程序通常的链接地址
+pub const ELF_ET_DYN_BASE: usize = 0x4000_0000;
+
+// 地址空间布局随机化 (ASLR)：用户栈的栈顶向下、用户堆、mmap 区域和 ET_DYN 程序的
+// 起始位置向上随机偏移的最大页数，分别是 64 MiB、32 MiB、1 GiB 和 1 GiB
+pub const ASLR_STACK_PAGES: usize = 0x4000;
+pub const ASLR_HEAP_PAGES: usize = 0x2000;
+pub const ASLR_MMAP_PAGES: usize = 0x4_0000;
+pub const ASLR_PIE_PAGES: usize = 0x4_0000;
+
+// W^X：不允许用户的 mmap 和 mprotect 建立同时可写和可执行的映射，
+// 需要生成代码的程序先写入，再通过 mprotect 改为可执行
+pub const USER_W_XOR_X: bool = true;
 
-// CLOCK_FREQ is clock frequency, in this case, the value is for qemu.
-pub const CLOCK_FREQ: usize = 12500000;
+// 交换区位于块设备上文件系统之后，需要与 easy-fs-fuse 生成的镜像保持一致
+pub const SWAP_START_BLOCK: usize = 16 * 2048;
+pub const SWAP_BLOCKS: usize = 16 * 2048;
 
-// MMIO for QEMU
-// the first one is started address, the second one is length
-pub const MMIO: &[(usize, usize)] = &[(0x10001000, 0x1000)];
+// 每次从用户态进入内核时至少保留的空闲页框，不够时先把用户页换出到交换区，
+// 这样处理缺页和系统调用时就不会因为没有页框而失败
+pub const RESERVED_FRAMES: usize = 16;
@@ -1,26 +1,17 @@
-use alloc::vec::Vec;
 use easy_fs::BlockDevice;
-use lazy_static::*;
-use virtio_drivers::{VirtIOBlk, VirtIOHeader};
+use virtio_drivers::{DeviceType, VirtIOBlk, VirtIOHeader};
 
 use crate::{
+    drivers::fdt,
     mm::{
-        address::{PhysAddr, PhysPageNum, StepByOne, VirtAddr},
-        frame_allocator::{frame_alloc, frame_dealloc},
+        address::{PhysAddr, VirtAddr},
+        frame_allocator::{frame_alloc_contiguous, frame_dealloc_contiguous},
         memory_set::kernel_token,
         page_table::PageTable,
-        FrameTracker,
     },
     sync::UPSafeCell,
 };
 
-// 参见 config::MMIO
-const VIRTIO0: usize = 0x10001000;
-
-lazy_static! {
-    static ref QUEUE_FRAMES: UPSafeCell<Vec<FrameTracker>> = unsafe { UPSafeCell::new(Vec::new()) };
-}
-
 // TODO(justxuewei): 查看 VirtIOBlk 是怎么实现的对块设备的抽象的？
 // virtio_drivers crate 提供了 VirtIO 块设备抽象，
 // VirtIO 是通过共享内存的方式实现的 qemu 和 guest 的 VirtQueue 互通。
@@ -28,12 +19,14 @@ pub struct VirtIOBlock(UPSafeCell<VirtIOBlk<'static>>);
 
 impl VirtIOBlock {
     pub fn new() -> Self {
-        unsafe {
-            Self(UPSafeCell::new(
-                // VirtIOHeader 代表以 MMIO 方式访问 VirtIO 设备所需的一组设备寄存器
-                VirtIOBlk::new(&mut *(VIRTIO0 as *mut VirtIOHeader)).unwrap(),
-            ))
-        }
+        // VirtIOHeader 代表以 MMIO 方式访问 VirtIO 设备所需的一组设备寄存器，
+        // 在设备树中的 virtio-mmio 设备中找到第一个块设备
+        let header = fdt::mmio_regions()
+            .into_iter()
+            .map(|(addr, _)| unsafe { &mut *(addr as *mut VirtIOHeader) })
+            .find(|header| header.verify() && matches!(header.device_type(), DeviceType::Block))
+            .expect("no virtio block device found");
+        unsafe { Self(UPSafeCell::new(VirtIOBlk::new(header).unwrap())) }
     }
 }
 
@@ -62,28 +55,17 @@ impl BlockDevice for VirtIOBlock {
 //    fn virtio_virt_to_phys(vaddr: VirtAddr) -> PhysAddr;
 // }
 
+// VirtQueue 需要物理上连续的页框
 #[no_mangle]
 pub extern "C" fn virtio_dma_alloc(pages: usize) -> PhysAddr {
-    let mut ppn_base = PhysPageNum(0);
-    for i in 0..pages {
-        let frame = frame_alloc().unwrap();
-        if i == 0 {
-            ppn_base = frame.ppn;
-        }
-        // VirtQueue 是一个环形结构，它的申请和释放都是线性的
-        assert_eq!(frame.ppn.0, ppn_base.0 + i);
-        QUEUE_FRAMES.exclusive_access().push(frame);
-    }
-    ppn_base.into()
+    frame_alloc_contiguous(pages, 1)
+        .expect("no contiguous frames for virtio dma")
+        .into()
 }
 
 #[no_mangle]
 pub extern "C" fn virtio_dma_dealloc(pa: PhysAddr, pages: usize) -> i32 {
-    let mut ppn_base: PhysPageNum = pa.into();
-    for _ in 0..pages {
-        frame_dealloc(ppn_base);
-        ppn_base.step();
-    }
+    frame_dealloc_contiguous(pa.into(), pages);
     0
 }
 
@@ -0,0 +1,224 @@
+use alloc::{string::String, vec::Vec};
+use core::str;
+use lazy_static::*;
+
+use crate::sync::UPSafeCell;
+
+// 扁平设备树 (Flattened Device Tree) 的格式参见
+// https://devicetree-specification.readthedocs.io/en/stable/flattened-format.html
+const FDT_MAGIC: u32 = 0xd00dfeed;
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+const FDT_END: u32 = 0x9;
+
+// 从设备树中得到的机器信息
+struct MachineInfo {
+    // 内核所在的物理内存区域的结束地址
+    memory_end: usize,
+    // virtio-mmio 设备的寄存器区域 (起始地址, 长度)
+    mmio: Vec<(usize, usize)>,
+    // time CSR 的频率
+    timebase_frequency: usize,
+    // /chosen/bootargs
+    bootargs: String,
+}
+
+lazy_static! {
+    static ref MACHINE_INFO: UPSafeCell<MachineInfo> = unsafe {
+        UPSafeCell::new(MachineInfo {
+            memory_end: 0,
+            mmio: Vec::new(),
+            timebase_frequency: 0,
+            bootargs: String::new(),
+        })
+    };
+}
+
+// 正在解析的节点，节点的属性都在子节点之前
+struct Node<'a> {
+    name: &'a str,
+    // 子节点的 reg 属性中地址和长度分别占用的 cell (u32) 数
+    address_cells: usize,
+    size_cells: usize,
+    reg: Option<&'a [u8]>,
+    compatible: Option<&'a [u8]>,
+    device_type: Option<&'a [u8]>,
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    strings: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        let value = Self::u32_at(self.data, self.pos);
+        self.pos += 4;
+        value
+    }
+
+    // 读取以 0 结尾的字符串，并把位置对齐到 4 字节
+    fn next_str(&mut self) -> &'a str {
+        let len = self.data[self.pos..].iter().position(|b| *b == 0).unwrap();
+        let s = str::from_utf8(&self.data[self.pos..self.pos + len]).unwrap_or("");
+        self.pos = (self.pos + len + 1 + 3) & !3;
+        s
+    }
+
+    fn next_bytes(&mut self, len: usize) -> &'a [u8] {
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos = (self.pos + len + 3) & !3;
+        bytes
+    }
+
+    fn string_at(&self, offset: usize) -> &'a str {
+        let bytes = &self.strings[offset..];
+        let len = bytes.iter().position(|b| *b == 0).unwrap();
+        str::from_utf8(&bytes[..len]).unwrap_or("")
+    }
+}
+
+// 把 cells 个 cell 组成的大端整数转换为 usize
+fn read_cells(bytes: &[u8], cells: usize) -> usize {
+    (0..cells).fold(0, |value, i| {
+        (value << 32) | Parser::u32_at(bytes, i * 4) as usize
+    })
+}
+
+// 解析 reg 属性中的 (地址, 长度) 对
+fn reg_entries(reg: &[u8], address_cells: usize, size_cells: usize) -> Vec<(usize, usize)> {
+    let entry_size = (address_cells + size_cells) * 4;
+    reg.chunks_exact(entry_size)
+        .map(|entry| {
+            (
+                read_cells(entry, address_cells),
+                read_cells(&entry[address_cells * 4..], size_cells),
+            )
+        })
+        .collect()
+}
+
+// 由以 0 分隔的字符串组成的属性中是否包含 s
+fn contains_str(list: Option<&[u8]>, s: &str) -> bool {
+    list.map_or(false, |list| {
+        list.split(|b| *b == 0).any(|item| item == s.as_bytes())
+    })
+}
+
+// 解析 SBI 固件通过 a1 寄存器传给内核的设备树，dtb 是设备树的物理地址。
+// 需要在页框分配器初始化之前调用，之后设备树所在的内存可能被分配出去。
+pub fn init(dtb: usize) {
+    extern "C" {
+        fn ekernel();
+    }
+    let header = unsafe { core::slice::from_raw_parts(dtb as *const u8, 40) };
+    assert_eq!(
+        Parser::u32_at(header, 0),
+        FDT_MAGIC,
+        "invalid device tree at {:#x}",
+        dtb
+    );
+    let total_size = Parser::u32_at(header, 4) as usize;
+    let data = unsafe { core::slice::from_raw_parts(dtb as *const u8, total_size) };
+    let strings_offset = Parser::u32_at(header, 12) as usize;
+    let mut parser = Parser {
+        data,
+        strings: &data[strings_offset..],
+        pos: Parser::u32_at(header, 8) as usize,
+    };
+
+    let mut info = MACHINE_INFO.exclusive_access();
+    let mut stack: Vec<Node> = Vec::new();
+    loop {
+        match parser.next_u32() {
+            FDT_BEGIN_NODE => {
+                let name = parser.next_str();
+                stack.push(Node {
+                    name,
+                    address_cells: 2,
+                    size_cells: 1,
+                    reg: None,
+                    compatible: None,
+                    device_type: None,
+                });
+            }
+            FDT_PROP => {
+                let len = parser.next_u32() as usize;
+                let name = parser.string_at(parser.next_u32() as usize);
+                let value = parser.next_bytes(len);
+                let in_chosen = stack.len() == 2 && stack[1].name == "chosen";
+                let in_cpus = stack.len() >= 2 && stack[1].name == "cpus";
+                let node = stack.last_mut().unwrap();
+                match name {
+                    "#address-cells" => node.address_cells = read_cells(value, 1),
+                    "#size-cells" => node.size_cells = read_cells(value, 1),
+                    "reg" => node.reg = Some(value),
+                    "compatible" => node.compatible = Some(value),
+                    "device_type" => node.device_type = Some(value),
+                    "bootargs" if in_chosen => {
+                        let len = value.iter().position(|b| *b == 0).unwrap_or(value.len());
+                        info.bootargs = String::from(str::from_utf8(&value[..len]).unwrap_or(""));
+                    }
+                    // timebase-frequency 可能在 /cpus 中，也可能在每个 cpu 节点中
+                    "timebase-frequency" if in_cpus && info.timebase_frequency == 0 => {
+                        info.timebase_frequency = read_cells(value, len / 4);
+                    }
+                    _ => {}
+                }
+            }
+            FDT_END_NODE => {
+                let node = stack.pop().unwrap();
+                let (reg, parent) = match (node.reg, stack.last()) {
+                    (Some(reg), Some(parent)) => (reg, parent),
+                    _ => continue,
+                };
+                let entries = reg_entries(reg, parent.address_cells, parent.size_cells);
+                if contains_str(node.device_type, "memory") {
+                    // 只使用内核所在的那一段物理内存
+                    for (start, len) in entries {
+                        if start <= ekernel as usize && (ekernel as usize) < start + len {
+                            info.memory_end = start + len;
+                        }
+                    }
+                } else if contains_str(node.compatible, "virtio,mmio") {
+                    info.mmio.extend(entries);
+                }
+            }
+            FDT_NOP => {}
+            FDT_END => break,
+            token => panic!("invalid device tree token {:#x}", token),
+        }
+    }
+    assert!(
+        info.memory_end != 0,
+        "no memory for the kernel in device tree"
+    );
+    assert!(
+        info.timebase_frequency != 0,
+        "no timebase-frequency in device tree"
+    );
+    info.mmio.sort();
+}
+
+pub fn memory_end() -> usize {
+    MACHINE_INFO.exclusive_access().memory_end
+}
+
+pub fn mmio_regions() -> Vec<(usize, usize)> {
+    MACHINE_INFO.exclusive_access().mmio.clone()
+}
+
+pub fn timebase_frequency() -> usize {
+    MACHINE_INFO.exclusive_access().timebase_frequency
+}
+
+pub fn bootargs() -> String {
+    MACHINE_INFO.exclusive_access().bootargs.clone()
+}
@@ -0,0 +1,26 @@
+// 系统调用出错时返回 -errno，取值与 Linux 保持一致
+
+/// 对象不存在，比如没有设置 IPC_CREAT 时共享内存段不存在
+pub const ENOENT: isize = 2;
+/// 进程或者进程组不存在
+pub const ESRCH: isize = 3;
+/// 被信号打断的系统调用
+pub const EINTR: isize = 4;
+/// 文件描述符不合法
+pub const EBADF: isize = 9;
+/// 资源暂时不可用，比如排队的实时信号已经达到上限
+pub const EAGAIN: isize = 11;
+/// 没有足够的内存，mprotect 的范围中有没有被映射的页时也返回它
+pub const ENOMEM: isize = 12;
+/// 没有权限，比如 W^X 不允许同时可写和可执行的映射
+pub const EACCES: isize = 13;
+/// 对象已经存在，比如设置了 IPC_EXCL 时共享内存段已经存在
+pub const EEXIST: isize = 17;
+/// 参数不合法
+pub const EINVAL: isize = 22;
+/// 文件不支持该 ioctl 命令，比如对非终端设备设置前台进程组
+pub const ENOTTY: isize = 25;
+
+/// 内核内部使用：阻塞的系统调用被信号打断，由信号处理流程决定是重新执行
+/// 系统调用（SA_RESTART）还是向用户返回 EINTR，不会被用户看到。
+pub const ERESTARTSYS: isize = 512;
@@ -1,9 +1,13 @@
-use alloc::{sync::Arc, vec::Vec};
+use alloc::sync::Arc;
 use bitflags::*;
-use easy_fs::{EasyFileSystem, Inode, BLOCK_SIZE};
+use easy_fs::{EasyFileSystem, Inode};
 use lazy_static::*;
 
-use crate::{drivers::block::BLOCK_DEVICE, mm::UserBuffer, sync::UPSafeCell};
+use crate::{
+    drivers::block::BLOCK_DEVICE,
+    mm::{page_cache, UserBuffer},
+    sync::UPSafeCell,
+};
 
 use super::File;
 
@@ -39,26 +43,10 @@ impl OSInode {
             inner: unsafe { UPSafeCell::new(OSInodeInner { offset: 0, inode }) },
         }
     }
-
-    // 读取 inode 对应的 data block 中的全部数据
-    pub fn read_all(&self) -> Vec<u8> {
-        let mut inner = self.inner.exclusive_access();
-        let mut buf = [0u8; BLOCK_SIZE];
-        let mut v: Vec<u8> = Vec::new();
-        loop {
-            let len = inner.inode.read_at(inner.offset, &mut buf);
-            if len == 0 {
-                break;
-            }
-            inner.offset += len;
-            v.extend_from_slice(&buf[..len]);
-        }
-        v
-    }
 }
 
 impl File for OSInode {
-    fn read(&self, mut buf: UserBuffer) -> usize {
+    fn read(&self, mut buf: UserBuffer) -> isize {
         let mut inner = self.inner.exclusive_access();
         let mut total_read_size = 0usize;
         for slice in buf.buffers.iter_mut() {
@@ -69,19 +57,20 @@ impl File for OSInode {
             inner.offset += read_size;
             total_read_size += read_size;
         }
-        total_read_size
+        total_read_size as isize
     }
 
-    fn write(&self, buf: UserBuffer) -> usize {
+    fn write(&self, buf: UserBuffer) -> isize {
         let mut inner = self.inner.exclusive_access();
         let mut total_write_size = 0usize;
         for slice in buf.buffers.iter() {
             let write_size = inner.inode.write_at(inner.offset, *slice);
             assert_eq!(write_size, slice.len());
+            page_cache::invalidate(&inner.inode, inner.offset, write_size);
             inner.offset += write_size;
             total_write_size += write_size;
         }
-        total_write_size
+        total_write_size as isize
     }
 
     fn readable(&self) -> bool {
@@ -91,6 +80,10 @@ impl File for OSInode {
     fn writable(&self) -> bool {
         self.writable
     }
+
+    fn inode(&self) -> Option<Arc<Inode>> {
+        Some(self.inner.exclusive_access().inode.clone())
+    }
 }
 
 pub fn list_apps() {
@@ -131,6 +124,7 @@ pub fn open_file(name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
         // 文件已经存在
         if let Some(inode) = ROOT_INODE.find(name) {
             inode.clear();
+            page_cache::invalidate_all(&inode);
             return Some(Arc::new(OSInode::new(readable, writable, inode)));
         }
         // 文件不存在，创建文件
@@ -143,6 +137,7 @@ pub fn open_file(name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
         // flags == TRUNCATE
         if flags.contains(OpenFlags::TRUNCATE) {
             inode.clear();
+            page_cache::invalidate_all(&inode);
         }
         Arc::new(OSInode::new(readable, writable, inode))
     })
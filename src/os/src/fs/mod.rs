@@ -1,17 +1,28 @@
-use crate::mm::UserBuffer;
+use alloc::sync::Arc;
+use easy_fs::Inode;
+
+use crate::{errno::ENOTTY, mm::UserBuffer};
 
 pub mod inode;
 pub mod pipe;
 pub mod stdio;
 
 pub use inode::open_file;
-pub use stdio::{Stdin, Stdout};
+pub use stdio::{poll_console_input, Stdin, Stdout};
 
 pub trait File: Send + Sync {
     fn readable(&self) -> bool;
     fn writable(&self) -> bool;
-    // read data from fs to buffer
-    fn read(&self, buf: UserBuffer) -> usize;
-    // write data from buffer to fs
-    fn write(&self, buf: UserBuffer) -> usize;
+    // read data from fs to buffer, returns -errno on error
+    fn read(&self, buf: UserBuffer) -> isize;
+    // write data from buffer to fs, returns -errno on error
+    fn write(&self, buf: UserBuffer) -> isize;
+    // device-specific control, files other than the console don't support it
+    fn ioctl(&self, _cmd: usize, _arg: usize) -> isize {
+        -ENOTTY
+    }
+    // the inode backing the file, only regular files can be mapped by mmap
+    fn inode(&self) -> Option<Arc<Inode>> {
+        None
+    }
 }
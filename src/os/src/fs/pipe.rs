@@ -1,6 +1,10 @@
 use alloc::sync::{Arc, Weak};
 
-use crate::{sync::UPSafeCell, task::suspend_current_and_run_next};
+use crate::{
+    errno::ERESTARTSYS,
+    sync::UPSafeCell,
+    task::{current_has_pending_signal, suspend_current_and_run_next},
+};
 
 use super::File;
 
@@ -33,7 +37,7 @@ impl Pipe {
 }
 
 impl File for Pipe {
-    fn read(&self, buf: crate::mm::UserBuffer) -> usize {
+    fn read(&self, buf: crate::mm::UserBuffer) -> isize {
         assert!(self.readable);
         let mut buf_iter = buf.into_iter();
         let mut read_size = 0usize;
@@ -43,9 +47,17 @@ impl File for Pipe {
             if loop_read_size == 0 {
                 // 写端已经关闭，不可能有新数据了
                 if pipe_buf.all_write_ends_closed() {
-                    return read_size;
+                    return read_size as isize;
                 }
                 drop(pipe_buf);
+                // 阻塞期间收到信号：已经读到数据时直接返回，否则交给信号处理流程
+                if current_has_pending_signal() {
+                    return if read_size > 0 {
+                        read_size as isize
+                    } else {
+                        -ERESTARTSYS
+                    };
+                }
                 suspend_current_and_run_next();
                 continue;
             }
@@ -56,13 +68,13 @@ impl File for Pipe {
                     read_size += 1;
                 } else {
                     // 用户 buffer 已满，没法写入任何新数据了
-                    return read_size;
+                    return read_size as isize;
                 }
             }
         }
     }
 
-    fn write(&self, buf: crate::mm::UserBuffer) -> usize {
+    fn write(&self, buf: crate::mm::UserBuffer) -> isize {
         assert!(self.writable);
         let mut buf_iter = buf.into_iter();
         let mut write_size = 0usize;
@@ -71,6 +83,13 @@ impl File for Pipe {
             let loop_write_size = pipe_buf.available_write();
             if loop_write_size == 0 {
                 drop(pipe_buf);
+                if current_has_pending_signal() {
+                    return if write_size > 0 {
+                        write_size as isize
+                    } else {
+                        -ERESTARTSYS
+                    };
+                }
                 suspend_current_and_run_next();
                 continue;
             }
@@ -79,7 +98,7 @@ impl File for Pipe {
                     unsafe { pipe_buf.write_byte(*byte_ptr) };
                     write_size += 1;
                 } else {
-                    return write_size;
+                    return write_size as isize;
                 }
             }
         }
@@ -1,7 +1,127 @@
-use crate::{mm::UserBuffer, sbi::console_getchar, task::suspend_current_and_run_next};
+use alloc::collections::VecDeque;
+use lazy_static::*;
+
+use crate::{
+    errno::{EINVAL, ENOTTY, ERESTARTSYS, ESRCH},
+    mm::UserBuffer,
+    sbi::console_getchar,
+    sync::UPSafeCell,
+    task::{
+        current_has_pending_signal, manager, processor::current_task, send_signal_to_group,
+        suspend_current_and_run_next, SignalFlags,
+    },
+};
 
 use super::File;
 
+// 控制字符，与 Linux 终端的默认设置一致
+const INTR_CHAR: u8 = 0x03; // Ctrl-C
+const QUIT_CHAR: u8 = 0x1c; // Ctrl-\
+const SUSP_CHAR: u8 = 0x1a; // Ctrl-Z
+
+// 控制台输入缓冲区的容量，超出时丢弃新的输入
+const CONSOLE_BUFFER_SIZE: usize = 4096;
+
+// ioctl 命令，取值与 Linux 保持一致
+const TIOCGPGRP: usize = 0x540f;
+const TIOCSPGRP: usize = 0x5410;
+
+// 控制台输入。输入在时钟中断和读取 stdin 时被取出，控制字符被转换为信号
+// 发送给前台进程组，其余字符保存在缓冲区中等待读取。
+struct ConsoleInput {
+    buffer: VecDeque<u8>,
+    // 前台进程组，为 0 时控制字符发送给正在等待输入的进程组
+    foreground_pgid: usize,
+    // 正在阻塞读取 stdin 的进程所在的进程组，为 0 表示没有进程在等待输入
+    reader_pgid: usize,
+}
+
+impl ConsoleInput {
+    // 控制字符转换成的信号发送给哪个进程组，为 0 时不转换
+    fn signal_pgid(&self) -> usize {
+        if self.foreground_pgid != 0 {
+            self.foreground_pgid
+        } else {
+            self.reader_pgid
+        }
+    }
+}
+
+lazy_static! {
+    static ref CONSOLE_INPUT: UPSafeCell<ConsoleInput> = unsafe {
+        UPSafeCell::new(ConsoleInput {
+            buffer: VecDeque::new(),
+            foreground_pgid: 0,
+            reader_pgid: 0,
+        })
+    };
+}
+
+/// 取出控制台全部待处理的输入
+pub fn poll_console_input() {
+    loop {
+        let c = console_getchar();
+        if c == 0 {
+            break;
+        }
+        let ch = c as u8;
+        let signal = match ch {
+            INTR_CHAR => Some(SignalFlags::SIGINT),
+            QUIT_CHAR => Some(SignalFlags::SIGQUIT),
+            SUSP_CHAR => Some(SignalFlags::SIGTSTP),
+            _ => None,
+        };
+        let mut input = CONSOLE_INPUT.exclusive_access();
+        match signal {
+            Some(flag) if input.signal_pgid() != 0 => {
+                let pgid = input.signal_pgid();
+                drop(input);
+                send_signal_to_group(pgid, flag);
+            }
+            _ => {
+                if input.buffer.len() < CONSOLE_BUFFER_SIZE {
+                    input.buffer.push_back(ch);
+                }
+            }
+        }
+    }
+}
+
+// 查询或者设置控制台的前台进程组，stdin 和 stdout 都指向控制台
+fn console_ioctl(cmd: usize, arg: usize) -> isize {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    match cmd {
+        TIOCGPGRP => {
+            let pgid = CONSOLE_INPUT.exclusive_access().foreground_pgid as i32;
+            if !task_inner.memory_set.copy_to_user(arg as *mut i32, &pgid) {
+                return -1;
+            }
+            0
+        }
+        TIOCSPGRP => {
+            let pgid = match task_inner.memory_set.copy_from_user(arg as *const i32) {
+                Some(pgid) if pgid >= 0 => pgid as usize,
+                Some(_) => return -EINVAL,
+                None => return -1,
+            };
+            drop(task_inner);
+            // 与 Linux 一致，只能设置为已经存在的进程组。内核没有实现会话，
+            // 所有进程都属于同一个会话，所以不会因为跨会话返回 EPERM。
+            if pgid == 0
+                || !manager::all_tasks()
+                    .iter()
+                    .any(|task| task.inner_exclusive_access().pgid == pgid)
+            {
+                return -ESRCH;
+            }
+            CONSOLE_INPUT.exclusive_access().foreground_pgid = pgid;
+            0
+        }
+        _ => -ENOTTY,
+    }
+}
+
 pub struct Stdin;
 
 impl File for Stdin {
@@ -13,28 +133,39 @@ impl File for Stdin {
         false
     }
 
-    fn read(&self, mut user_buf: UserBuffer) -> usize {
+    fn read(&self, mut user_buf: UserBuffer) -> isize {
         assert_eq!(user_buf.len(), 1);
-        let mut c: usize;
-        loop {
-            c = console_getchar();
-            if c == 0 {
-                suspend_current_and_run_next();
-                continue;
-            } else {
-                break;
+        let pgid = current_task().unwrap().inner_exclusive_access().pgid;
+        let ch = loop {
+            poll_console_input();
+            let mut input = CONSOLE_INPUT.exclusive_access();
+            if let Some(ch) = input.buffer.pop_front() {
+                input.reader_pgid = 0;
+                break ch;
             }
-        }
-        let ch = c as u8;
+            // 等待输入期间收到信号，交给信号处理流程决定是否重新读取
+            if current_has_pending_signal() {
+                input.reader_pgid = 0;
+                return -ERESTARTSYS;
+            }
+            // 没有设置前台进程组时，控制字符发送给等待输入的进程组
+            input.reader_pgid = pgid;
+            drop(input);
+            suspend_current_and_run_next();
+        };
         unsafe {
             user_buf.buffers[0].as_mut_ptr().write_volatile(ch);
         }
         1
     }
 
-    fn write(&self, _user_buf: UserBuffer) -> usize {
+    fn write(&self, _user_buf: UserBuffer) -> isize {
         panic!("Cannot write to stdin!");
     }
+
+    fn ioctl(&self, cmd: usize, arg: usize) -> isize {
+        console_ioctl(cmd, arg)
+    }
 }
 
 pub struct Stdout;
@@ -48,14 +179,18 @@ impl File for Stdout {
         true
     }
 
-    fn read(&self, _user_buf: UserBuffer) -> usize {
+    fn read(&self, _user_buf: UserBuffer) -> isize {
         panic!("Cannot read from stdout!");
     }
 
-    fn write(&self, user_buf: UserBuffer) -> usize {
+    fn write(&self, user_buf: UserBuffer) -> isize {
         for buffer in user_buf.buffers.iter() {
             print!("{}", core::str::from_utf8(*buffer).unwrap());
         }
-        user_buf.len()
+        user_buf.len() as isize
+    }
+
+    fn ioctl(&self, cmd: usize, arg: usize) -> isize {
+        console_ioctl(cmd, arg)
     }
 }
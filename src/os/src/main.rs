@@ -10,9 +10,11 @@
 mod console;
 mod config;
 mod drivers;
+mod errno;
 pub mod fs;
 mod lang_items;
 mod mm;
+mod random;
 mod sbi;
 mod sync;
 pub mod syscall;
@@ -30,11 +32,21 @@ global_asm!(include_str!("entry.asm"));
 // 将用户程序链接到操作系统中
 global_asm!(include_str!("link_app.S"));
 
+// SBI 固件把 hart id 和设备树的物理地址分别通过 a0 和 a1 传给内核
 #[no_mangle]
-fn rust_main() -> ! {
+fn rust_main(_hart_id: usize, dtb: usize) -> ! {
     clear_bss();
 
     println!("[kernel] Welcome to rCore!");
+    mm::init_heap();
+    drivers::fdt::init(dtb);
+    let bootargs = drivers::fdt::bootargs();
+    println!("[kernel] bootargs: {}", bootargs);
+    random::init();
+    // 与 Linux 一样，启动参数 norandmaps 关闭用户地址空间布局随机化
+    mm::memory_set::set_randomize_va_space(
+        !bootargs.split_whitespace().any(|arg| arg == "norandmaps"),
+    );
     mm::init();
     task::add_initproc();
     trap::init();
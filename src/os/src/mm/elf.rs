@@ -0,0 +1,159 @@
+use alloc::{vec, vec::Vec};
+use easy_fs::Inode;
+use xmas_elf::{program::Type, ElfFile};
+
+// 辅助向量 (auxiliary vector) 的类型，与 Linux 的编码一致
+pub const AT_NULL: usize = 0;
+pub const AT_PHDR: usize = 3;
+pub const AT_PHENT: usize = 4;
+pub const AT_PHNUM: usize = 5;
+pub const AT_PAGESZ: usize = 6;
+pub const AT_BASE: usize = 7;
+pub const AT_ENTRY: usize = 9;
+
+// exec 时放在用户栈上 argv 之后的一项辅助向量，以 AT_NULL 结尾
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct AuxHeader {
+    pub aux_type: usize,
+    pub value: usize,
+}
+
+impl AuxHeader {
+    pub fn new(aux_type: usize, value: usize) -> Self {
+        Self { aux_type, value }
+    }
+}
+
+// .dynamic 中与重定位相关的标签
+const DT_NULL: u64 = 0;
+const DT_RELA: u64 = 7;
+const DT_RELASZ: u64 = 8;
+const DT_RELAENT: u64 = 9;
+
+const R_RISCV_NONE: u64 = 0;
+const R_RISCV_RELATIVE: u64 = 3;
+
+// 一项 R_RISCV_RELATIVE 重定位：把 offset 处的 8 字节改为加载基址加上 addend
+pub struct Relocation {
+    pub offset: usize,
+    pub addend: usize,
+}
+
+// elf header 的大小，以及其中 program headers 的位置和大小所在的偏移
+const ELF_HEADER_SIZE: usize = 64;
+const E_PHOFF: usize = 0x20;
+const E_PHENTSIZE: usize = 0x36;
+const E_PHNUM: usize = 0x38;
+
+// 读取文件 inode 中 [offset, offset + len) 的内容。这些位置和长度来自 elf 文件，
+// 不可信，超出文件末尾时返回 None，避免按照伪造的长度分配内存。
+fn read_file(inode: &Inode, offset: usize, len: usize) -> Option<Vec<u8>> {
+    let end = offset.checked_add(len)?;
+    if end > inode.size() {
+        return None;
+    }
+    let mut data = vec![0u8; len];
+    inode.read_at(offset, &mut data);
+    Some(data)
+}
+
+// 读取 elf 文件开头的 elf header 和 program headers，加载程序只需要这些内容，
+// 段的数据在缺页时再读取。program headers 超出文件末尾时返回 None。
+pub fn read_headers(inode: &Inode) -> Option<Vec<u8>> {
+    let header = read_file(inode, 0, ELF_HEADER_SIZE)?;
+    let ph_offset = read_u64(&header, E_PHOFF)? as usize;
+    let ph_entry_size = u16::from_le_bytes([header[E_PHENTSIZE], header[E_PHENTSIZE + 1]]);
+    let ph_count = u16::from_le_bytes([header[E_PHNUM], header[E_PHNUM + 1]]);
+    let end = (ph_entry_size as usize)
+        .checked_mul(ph_count as usize)?
+        .checked_add(ph_offset)?;
+    read_file(inode, 0, end.max(ELF_HEADER_SIZE))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset.checked_add(8)?)?;
+    Some(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+// 把链接地址 va 转换成它在 elf 文件中的偏移，va 不在任何 LOAD 段的文件内容中时返回 None
+fn va_to_offset(elf: &ElfFile, va: u64) -> Option<usize> {
+    elf.program_iter()
+        .filter(|ph| ph.get_type() == Ok(Type::Load))
+        .find(|ph| va >= ph.virtual_addr() && va - ph.virtual_addr() < ph.file_size())
+        .and_then(|ph| (va - ph.virtual_addr()).checked_add(ph.offset()))
+        .map(|offset| offset as usize)
+}
+
+// 程序头表被加载到的链接地址，供 AT_PHDR 使用。
+// 优先使用 PT_PHDR，否则在包含程序头表的 LOAD 段中计算。
+pub fn phdr_va(elf: &ElfFile) -> Option<usize> {
+    let ph_offset = elf.header.pt2.ph_offset();
+    if let Some(ph) = elf
+        .program_iter()
+        .find(|ph| ph.get_type() == Ok(Type::Phdr))
+    {
+        return Some(ph.virtual_addr() as usize);
+    }
+    elf.program_iter()
+        .filter(|ph| ph.get_type() == Ok(Type::Load))
+        .find(|ph| ph_offset >= ph.offset() && ph_offset - ph.offset() < ph.file_size())
+        .map(|ph| ph.virtual_addr().wrapping_add(ph_offset - ph.offset()) as usize)
+}
+
+// 从文件 inode 中读取 PT_DYNAMIC 中 DT_RELA 指向的重定位表，elf 只需要包含
+// program headers。static-pie 程序只需要 R_RISCV_RELATIVE，其它类型的重定位
+// 需要动态链接器，打印警告后忽略。.dynamic 或者重定位表超出文件末尾时返回 None。
+pub fn relocations(elf: &ElfFile, inode: &Inode) -> Option<Vec<Relocation>> {
+    let mut relocations = Vec::new();
+    let dynamic = match elf
+        .program_iter()
+        .find(|ph| ph.get_type() == Ok(Type::Dynamic))
+    {
+        Some(ph) => ph,
+        None => return Some(relocations),
+    };
+    let (mut rela, mut rela_size, mut rela_ent) = (None, 0, 24);
+    let data = read_file(
+        inode,
+        dynamic.offset() as usize,
+        dynamic.file_size() as usize,
+    )?;
+    for entry in (0..data.len()).step_by(16) {
+        let (tag, value) = match (read_u64(&data, entry), read_u64(&data, entry + 8)) {
+            (Some(tag), Some(value)) => (tag, value),
+            _ => break,
+        };
+        match tag {
+            DT_NULL => break,
+            DT_RELA => rela = va_to_offset(elf, value),
+            DT_RELASZ => rela_size = value as usize,
+            DT_RELAENT => rela_ent = value as usize,
+            _ => {}
+        }
+    }
+    let rela = match rela {
+        Some(rela) if rela_ent >= 24 => rela,
+        _ => return Some(relocations),
+    };
+    let data = read_file(inode, rela, rela_size)?;
+    for entry in (0..rela_size).step_by(rela_ent) {
+        let (offset, info, addend) = match (
+            read_u64(&data, entry),
+            read_u64(&data, entry + 8),
+            read_u64(&data, entry + 16),
+        ) {
+            (Some(offset), Some(info), Some(addend)) => (offset, info, addend),
+            _ => break,
+        };
+        match info & 0xffff_ffff {
+            R_RISCV_NONE => {}
+            R_RISCV_RELATIVE => relocations.push(Relocation {
+                offset: offset as usize,
+                addend: addend as usize,
+            }),
+            kind => println!("[kernel] unsupported relocation type {}, ignored", kind),
+        }
+    }
+    Some(relocations)
+}
@@ -1,11 +1,14 @@
 use core::fmt::{self, Debug, Formatter};
 
-use alloc::vec::Vec;
+use alloc::{collections::BTreeSet, vec::Vec};
 use lazy_static::*;
 
-use crate::{config, sync::UPSafeCell};
+use crate::{drivers::fdt, sync::UPSafeCell, task};
 
-use super::address::{PhysAddr, PhysPageNum};
+use super::{
+    address::{PhysAddr, PhysPageNum},
+    page_cache,
+};
 
 pub struct FrameTracker {
     pub ppn: PhysPageNum,
@@ -40,52 +43,158 @@ trait FrameAllocator {
     fn dealloc(&mut self, ppn: PhysPageNum);
 }
 
-pub struct StackFrameAllocator {
-    current: usize,
+// 伙伴系统中最大的块包含 2^MAX_ORDER 个页框
+const MAX_ORDER: usize = 11;
+
+// BuddyFrameAllocator 按照伙伴系统管理物理页框，可以分配连续的页框。
+// 每一个阶 (order) 维护一个空闲链表，阶为 order 的块包含 2^order 个页框，
+// 起始 ppn 按照块的大小对齐。释放时与空闲的伙伴块合并成更大的块。
+pub struct BuddyFrameAllocator {
+    // 管理的页框范围 [base, end)
+    base: usize,
     end: usize,
-    recycled: Vec<usize>,
+    // free_lists[order] 保存阶为 order 的空闲块的起始 ppn
+    free_lists: Vec<BTreeSet<usize>>,
+    // 每个页框占一位，置位表示页框已经被分配，用来检查重复释放
+    allocated: Vec<u64>,
+    free: usize,
+}
+
+// 物理页框的使用情况
+#[derive(Debug, Clone, Copy)]
+pub struct FrameStats {
+    pub total: usize,
+    pub free: usize,
+    // 最大的空闲块包含的页框数，即目前能分配的最长连续页框
+    pub largest_free_block: usize,
 }
 
-impl FrameAllocator for StackFrameAllocator {
+impl FrameAllocator for BuddyFrameAllocator {
     fn new() -> Self {
         Self {
-            current: 0,
+            base: 0,
             end: 0,
-            recycled: Vec::new(),
+            free_lists: (0..=MAX_ORDER).map(|_| BTreeSet::new()).collect(),
+            allocated: Vec::new(),
+            free: 0,
         }
     }
 
     fn alloc(&mut self) -> Option<PhysPageNum> {
-        if let Some(ppn) = self.recycled.pop() {
-            Some(ppn.into())
-        } else if self.current == self.end {
-            None
-        } else {
-            self.current += 1;
-            Some((self.current - 1).into())
-        }
+        self.alloc_contiguous(1, 1)
     }
 
     fn dealloc(&mut self, ppn: PhysPageNum) {
-        let ppn = ppn.0;
-        // validity check
-        if ppn >= self.current || self.recycled.iter().any(|&v| v == ppn) {
-            panic!("Frame ppn={:#x} has not been allocated!", ppn);
-        }
-        self.recycled.push(ppn);
+        self.dealloc_contiguous(ppn, 1)
     }
 }
 
-impl StackFrameAllocator {
+impl BuddyFrameAllocator {
     fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
-        self.current = l.0;
+        self.base = l.0;
         self.end = r.0;
+        self.allocated = vec![0; (r.0 - l.0 + 63) / 64];
+        self.free_range(l.0, r.0);
+    }
+
+    fn free_count(&self) -> usize {
+        self.free
+    }
+
+    fn stats(&self) -> FrameStats {
+        FrameStats {
+            total: self.end - self.base,
+            free: self.free,
+            largest_free_block: (0..=MAX_ORDER)
+                .rev()
+                .find(|order| !self.free_lists[*order].is_empty())
+                .map_or(0, |order| 1 << order),
+        }
+    }
+
+    // 分配 pages 个连续的页框，起始 ppn 按照 align 个页框对齐，align 必须是 2 的幂
+    fn alloc_contiguous(&mut self, pages: usize, align: usize) -> Option<PhysPageNum> {
+        assert!(
+            align.is_power_of_two(),
+            "align {} is not a power of 2",
+            align
+        );
+        if pages == 0 {
+            return None;
+        }
+        let order = pages.next_power_of_two().max(align).trailing_zeros() as usize;
+        // 找到一个足够大的空闲块，把多余的部分逐阶拆分成伙伴块放回空闲链表
+        let found = (order..=MAX_ORDER).find(|o| !self.free_lists[*o].is_empty())?;
+        let start = *self.free_lists[found].iter().next().unwrap();
+        self.free_lists[found].remove(&start);
+        for o in (order..found).rev() {
+            self.free_lists[o].insert(start + (1 << o));
+        }
+        // 块中超出 pages 的页框立即归还
+        self.free -= 1 << order;
+        self.free_range(start + pages, start + (1 << order));
+        for ppn in start..start + pages {
+            self.set_allocated(ppn, true);
+        }
+        Some(start.into())
+    }
+
+    fn dealloc_contiguous(&mut self, ppn: PhysPageNum, pages: usize) {
+        let start = ppn.0;
+        // validity check
+        for ppn in start..start + pages {
+            if ppn < self.base || ppn >= self.end || !self.is_allocated(ppn) {
+                panic!("Frame ppn={:#x} has not been allocated!", ppn);
+            }
+            self.set_allocated(ppn, false);
+        }
+        self.free_range(start, start + pages);
+    }
+
+    // 把 [start, end) 按照对齐拆分成尽量大的块放回空闲链表
+    fn free_range(&mut self, mut start: usize, end: usize) {
+        while start < end {
+            let order = (0..=MAX_ORDER)
+                .rev()
+                .find(|o| start % (1 << o) == 0 && start + (1 << o) <= end)
+                .unwrap();
+            self.free_block(start, order);
+            start += 1 << order;
+        }
+    }
+
+    // 释放一个阶为 order 的块，伙伴块也空闲时合并为阶为 order + 1 的块
+    fn free_block(&mut self, mut start: usize, mut order: usize) {
+        self.free += 1 << order;
+        while order < MAX_ORDER {
+            let buddy = start ^ (1 << order);
+            if !self.free_lists[order].remove(&buddy) {
+                break;
+            }
+            start = start.min(buddy);
+            order += 1;
+        }
+        self.free_lists[order].insert(start);
+    }
+
+    fn is_allocated(&self, ppn: usize) -> bool {
+        let idx = ppn - self.base;
+        self.allocated[idx / 64] & (1 << (idx % 64)) != 0
+    }
+
+    fn set_allocated(&mut self, ppn: usize, allocated: bool) {
+        let idx = ppn - self.base;
+        if allocated {
+            self.allocated[idx / 64] |= 1 << (idx % 64);
+        } else {
+            self.allocated[idx / 64] &= !(1 << (idx % 64));
+        }
     }
 }
 
 lazy_static! {
-    pub static ref FRAME_ALLOCATOR: UPSafeCell<StackFrameAllocator> =
-        unsafe { UPSafeCell::new(StackFrameAllocator::new()) };
+    pub static ref FRAME_ALLOCATOR: UPSafeCell<BuddyFrameAllocator> =
+        unsafe { UPSafeCell::new(BuddyFrameAllocator::new()) };
 }
 
 pub fn init_frame_allocator() {
@@ -94,17 +203,80 @@ pub fn init_frame_allocator() {
     }
     FRAME_ALLOCATOR.exclusive_access().init(
         PhysAddr::from(ekernel as usize).ceil(),
-        PhysAddr::from(config::MEMORY_END).floor(),
-    )
+        PhysAddr::from(fdt::memory_end()).floor(),
+    );
+    let stats = frame_stats();
+    println!(
+        "[kernel] frame allocator: {} frames, {} free, largest free block {} frames",
+        stats.total, stats.free, stats.largest_free_block
+    );
 }
 
 pub fn frame_alloc() -> Option<FrameTracker> {
+    // 没有空闲的页框时先把用户页换出到交换区
+    if !frame_reserve(1) {
+        return None;
+    }
     FRAME_ALLOCATOR
         .exclusive_access()
         .alloc()
         .map(FrameTracker::new)
 }
 
+// 确保至少有 count 个空闲页框，不够时先丢弃页缓存中没有被映射的页，
+// 再把用户页换出到交换区。返回 false 表示已经没有可以换出的页了。
+pub fn frame_reserve(count: usize) -> bool {
+    loop {
+        let free = FRAME_ALLOCATOR.exclusive_access().free_count();
+        if free >= count {
+            return true;
+        }
+        if page_cache::shrink(count - free) > 0 {
+            continue;
+        }
+        if task::reclaim_frames(count - free) == 0 {
+            return false;
+        }
+    }
+}
+
+// 分配 pages 个连续的页框并初始化为 0，起始 ppn 按照 align 个页框对齐。
+// 连续的页框由调用者通过 frame_dealloc_contiguous 释放。
+pub fn frame_alloc_contiguous(pages: usize, align: usize) -> Option<PhysPageNum> {
+    if !frame_reserve(pages) {
+        return None;
+    }
+    let ppn = FRAME_ALLOCATOR
+        .exclusive_access()
+        .alloc_contiguous(pages, align)?;
+    for i in 0..pages {
+        PhysPageNum(ppn.0 + i).get_bytes_array().fill(0);
+    }
+    Some(ppn)
+}
+
+// 内核堆使用的连续页框分配，不换出用户页也不清零。
+// 页框分配器正在被使用时（比如它自己在申请堆空间）返回 None。
+pub fn frame_try_alloc_contiguous(pages: usize, align: usize) -> Option<PhysPageNum> {
+    FRAME_ALLOCATOR
+        .try_exclusive_access()?
+        .alloc_contiguous(pages, align)
+}
+
+pub fn frame_dealloc_contiguous(ppn: PhysPageNum, pages: usize) {
+    FRAME_ALLOCATOR
+        .exclusive_access()
+        .dealloc_contiguous(ppn, pages)
+}
+
+pub fn frame_stats() -> FrameStats {
+    FRAME_ALLOCATOR.exclusive_access().stats()
+}
+
+pub fn frame_free_count() -> usize {
+    FRAME_ALLOCATOR.exclusive_access().free_count()
+}
+
 pub fn frame_dealloc(ppn: PhysPageNum) {
     FRAME_ALLOCATOR.exclusive_access().dealloc(ppn)
 }
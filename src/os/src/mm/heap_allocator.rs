@@ -1,12 +1,111 @@
-use crate::config;
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::config::{self, PAGE_SIZE};
 use buddy_system_allocator as sysalloc;
 
+use super::{address::PhysAddr, frame_allocator};
+
+// 空闲的堆空间少于 HEAP_LOW_WATERMARK 时提前增长，这样在增长的过程中
+// （比如页框分配器自己申请堆空间）总是有足够的堆空间可以使用
+const HEAP_LOW_WATERMARK: usize = 0x1_0000;
+
+// GrowableHeap 在静态的 HEAP_SPACE 用完之后，向页框分配器申请连续的页框加入堆中。
+// 内核地址空间恒等映射了所有的物理内存，所以这些页框可以直接通过物理地址访问。
+struct GrowableHeap {
+    heap: sysalloc::LockedHeap,
+    // 正在增长时不再嵌套地增长
+    growing: AtomicBool,
+}
+
 #[global_allocator]
-static HEAP_ALLOCATOR: sysalloc::LockedHeap = sysalloc::LockedHeap::empty();
+static HEAP_ALLOCATOR: GrowableHeap = GrowableHeap {
+    heap: sysalloc::LockedHeap::empty(),
+    growing: AtomicBool::new(false),
+};
+
+unsafe impl GlobalAlloc for GrowableHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let result = self.heap.lock().alloc(layout);
+        let ptr = match result {
+            Ok(ptr) => ptr.as_ptr(),
+            Err(_) => {
+                if !self.grow(layout.size().max(layout.align())) {
+                    return ptr::null_mut();
+                }
+                let result = self.heap.lock().alloc(layout);
+                result.map_or(ptr::null_mut(), |ptr| ptr.as_ptr())
+            }
+        };
+        if self.free_bytes() < HEAP_LOW_WATERMARK {
+            self.grow(0);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.heap
+            .lock()
+            .dealloc(NonNull::new_unchecked(ptr), layout)
+    }
+}
+
+impl GrowableHeap {
+    fn free_bytes(&self) -> usize {
+        let heap = self.heap.lock();
+        heap.stats_total_bytes() - heap.stats_alloc_actual()
+    }
+
+    // 把至少 size 字节的连续页框加入堆中。页框按照大小对齐，这样大的分配也能得到满足。
+    // 已经在增长或者没有足够的连续页框时返回 false。
+    fn grow(&self, size: usize) -> bool {
+        if self.growing.swap(true, Ordering::Acquire) {
+            return false;
+        }
+        let pages = size.max(config::KERNEL_HEAP_GROW_SIZE).next_power_of_two() / PAGE_SIZE;
+        let result = frame_allocator::frame_try_alloc_contiguous(pages, pages);
+        if let Some(ppn) = result {
+            let start: usize = PhysAddr::from(ppn).into();
+            unsafe {
+                self.heap
+                    .lock()
+                    .add_to_heap(start, start + pages * PAGE_SIZE)
+            };
+        }
+        self.growing.store(false, Ordering::Release);
+        result.is_some()
+    }
+}
+
+// 内核堆的使用情况（字节）
+pub struct HeapStats {
+    // 堆的总大小
+    pub total: usize,
+    // 实际分配出去的大小，包括按 2 的幂向上取整的部分
+    pub allocated: usize,
+    // 调用者申请的大小
+    pub requested: usize,
+}
+
+pub fn heap_stats() -> HeapStats {
+    let heap = HEAP_ALLOCATOR.heap.lock();
+    HeapStats {
+        total: heap.stats_total_bytes(),
+        allocated: heap.stats_alloc_actual(),
+        requested: heap.stats_alloc_user(),
+    }
+}
 
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
-    panic!("Failed to allocate on heap, layout = {:?}", layout);
+    let stats = heap_stats();
+    panic!(
+        "Failed to allocate on heap, layout = {:?}, heap total = {:#x}, allocated = {:#x}, requested = {:#x}",
+        layout, stats.total, stats.allocated, stats.requested
+    );
 }
 
 static mut HEAP_SPACE: [u8; config::KERNEL_HEAP_SIZE] = [0; config::KERNEL_HEAP_SIZE];
@@ -14,6 +113,7 @@ static mut HEAP_SPACE: [u8; config::KERNEL_HEAP_SIZE] = [0; config::KERNEL_HEAP_
 pub fn init_heap() {
     unsafe {
         HEAP_ALLOCATOR
+            .heap
             .lock()
             .init(HEAP_SPACE.as_ptr() as usize, config::KERNEL_HEAP_SIZE)
     }
@@ -1,18 +1,38 @@
-use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    vec::Vec,
+};
 use bitflags::*;
-use core::arch::asm;
+use core::{
+    arch::asm,
+    mem::size_of,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use easy_fs::Inode;
 use lazy_static::*;
 
 use crate::{
-    config::{self, MEMORY_END, PAGE_SIZE, TRAMPOLINE},
+    config::{
+        self, ASLR_HEAP_PAGES, ASLR_MMAP_PAGES, ASLR_PIE_PAGES, ASLR_STACK_PAGES, ELF_ET_DYN_BASE,
+        MMAP_BASE, PAGE_SIZE, RESERVED_FRAMES, SIGRETURN_TRAMPOLINE, TRAMPOLINE, USER_STACK_LIMIT,
+        USER_STACK_TOP,
+    },
+    drivers::fdt,
+    errno::{EACCES, ENOMEM},
     mm::address::StepByOne,
+    random,
     sync::UPSafeCell,
 };
 
 use super::{
     address::{PhysAddr, PhysPageNum, VPNRange, VirtAddr, VirtPageNum},
-    frame_allocator::{frame_alloc, FrameTracker},
-    page_table::{PTEFlags, PageTable, PageTableEntry},
+    elf::{self, AuxHeader, AT_BASE, AT_ENTRY, AT_PAGESZ, AT_PHDR, AT_PHENT, AT_PHNUM},
+    frame_allocator::{frame_alloc, frame_free_count, FrameTracker},
+    page_cache,
+    page_table::{self, PTEFlags, PageTable, PageTableEntry},
+    shm::SharedMemory,
+    swap,
 };
 
 extern "C" {
@@ -26,6 +46,7 @@ extern "C" {
     fn ebss();
     fn ekernel();
     fn strampoline();
+    fn ssigreturn();
 }
 
 lazy_static! {
@@ -33,15 +54,56 @@ lazy_static! {
         Arc::new(unsafe { UPSafeCell::new(MemorySet::new_kernel()) });
 }
 
+// 是否对用户地址空间的布局进行随机化，启动参数中有 norandmaps 时关闭
+static RANDOMIZE_VA_SPACE: AtomicBool = AtomicBool::new(true);
+
+pub fn set_randomize_va_space(enabled: bool) {
+    RANDOMIZE_VA_SPACE.store(enabled, Ordering::Relaxed);
+}
+
 // Get kernel space root ppn
 pub fn kernel_token() -> usize {
     KERNEL_SPACE.exclusive_access().token()
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone)]
 pub enum MapType {
     Identical, // 一个 VPN 唯一的映射一个 PPN，比如内核就需要访问物理内存中的某个 PPN
     Framed,    // 一个 VPN 随机的映射一个 PPN
+    // 映射文件 inode 从 offset 开始的内容，页在第一次被访问时才从文件中读取。
+    // shared 为 true 时（MAP_SHARED）被修改过的页会写回文件；为 false 时
+    // （MAP_PRIVATE 和程序的段）直接映射页缓存中的页，第一次写入时才复制。
+    // fork 出的子进程与父进程共享 MAP_SHARED 的页框，各自记录自己写过的脏页。
+    File {
+        inode: Arc<Inode>,
+        offset: usize,
+        shared: bool,
+    },
+    // 共享内存段从 offset 开始的部分，页框属于共享内存段，映射时不会分配新的页框。
+    // fork 出的子进程映射同样的页框。
+    Shared {
+        shm: Arc<SharedMemory>,
+        offset: usize,
+    },
+    // 与 Framed 相同，但是页框在第一次被访问时才分配并初始化为 0。
+    // data 不为空时（比如 ELF 的段中不能直接映射页缓存的部分），逻辑段的前 len 个
+    // 字节来自文件 data 从 offset 开始的内容，在缺页时读取到对应的页中。
+    Lazy {
+        data: Option<Arc<Inode>>,
+        offset: usize,
+        len: usize,
+    },
+}
+
+impl MapType {
+    // 初始化为 0 的惰性逻辑段，比如用户堆和用户栈
+    pub fn zeroed() -> Self {
+        MapType::Lazy {
+            data: None,
+            offset: 0,
+            len: 0,
+        }
+    }
 }
 
 bitflags! {
@@ -54,11 +116,39 @@ bitflags! {
     }
 }
 
+// 加载程序失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadError {
+    // elf 文件不合法，比如长度或者偏移超出了文件末尾
+    InvalidElf,
+    // 没有空闲的页框
+    OutOfMemory,
+}
+
+// 缺页无法被处理的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageFaultError {
+    // 地址没有被映射或者权限不足，是一次非法访问
+    Illegal,
+    // 没有空闲的页框，换出页和丢弃页缓存之后依然不够
+    OutOfMemory,
+}
+
 pub struct MapArea {
     vpn_range: VPNRange,
-    data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    // 逻辑段自己的页框。共享的文件映射中的页框在 fork 之后被父子进程共享，
+    // 其余的页框只属于这个逻辑段
+    data_frames: BTreeMap<VirtPageNum, Arc<FrameTracker>>,
     map_type: MapType,
     map_perm: MapPermission,
+    // mprotect 可以设置的最大权限，比如只读打开的文件的共享映射不能被改为可写
+    max_perm: MapPermission,
+    // 共享文件映射中被修改过、还没有写回文件的页
+    dirty_pages: BTreeSet<VirtPageNum>,
+    // 惰性逻辑段中被换出到交换区的页及其所在的 slot
+    swapped: BTreeMap<VirtPageNum, usize>,
+    // 私有文件映射中直接映射的页缓存中的页，写入之前一直是只读的
+    cached_pages: BTreeMap<VirtPageNum, Arc<FrameTracker>>,
 }
 
 impl MapArea {
@@ -73,6 +163,10 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type: map_type,
             map_perm: map_perm,
+            max_perm: MapPermission::all(),
+            dirty_pages: BTreeSet::new(),
+            swapped: BTreeMap::new(),
+            cached_pages: BTreeMap::new(),
         }
     }
 
@@ -82,41 +176,219 @@ impl MapArea {
         Self {
             vpn_range: VPNRange::new(map_area.vpn_range.get_start(), map_area.vpn_range.get_end()),
             data_frames: BTreeMap::new(),
-            map_type: map_area.map_type,
+            map_type: map_area.map_type.clone(),
             map_perm: map_area.map_perm,
+            max_perm: map_area.max_perm,
+            dirty_pages: BTreeSet::new(),
+            swapped: BTreeMap::new(),
+            cached_pages: BTreeMap::new(),
         }
     }
 
+    // 从 vpn 处把逻辑段一分为二，当前逻辑段保留 [start, vpn)，返回 [vpn, end)。
+    // 页表中的映射关系不变，只是页框的所有权随之转移。
+    pub fn split_off(&mut self, vpn: VirtPageNum) -> Self {
+        let start = self.vpn_range.get_start();
+        let end = self.vpn_range.get_end();
+        assert!(start < vpn && vpn < end, "split {:?} out of area", vpn);
+        self.vpn_range = VPNRange::new(start, vpn);
+        let map_type = match &self.map_type {
+            MapType::File {
+                inode,
+                offset,
+                shared,
+            } => MapType::File {
+                inode: inode.clone(),
+                offset: offset + (vpn.0 - start.0) * PAGE_SIZE,
+                shared: *shared,
+            },
+            MapType::Shared { shm, offset } => MapType::Shared {
+                shm: shm.clone(),
+                offset: offset + (vpn.0 - start.0) * PAGE_SIZE,
+            },
+            MapType::Lazy { data, offset, len } => {
+                let skipped = (vpn.0 - start.0) * PAGE_SIZE;
+                MapType::Lazy {
+                    data: data.clone(),
+                    offset: offset + skipped,
+                    len: len.saturating_sub(skipped),
+                }
+            }
+            map_type => map_type.clone(),
+        };
+        Self {
+            vpn_range: VPNRange::new(vpn, end),
+            data_frames: self.data_frames.split_off(&vpn),
+            map_type,
+            map_perm: self.map_perm,
+            max_perm: self.max_perm,
+            dirty_pages: self.dirty_pages.split_off(&vpn),
+            swapped: self.swapped.split_off(&vpn),
+            cached_pages: self.cached_pages.split_off(&vpn),
+        }
+    }
+
+    // 逻辑段是否与 [start, end) 有重叠
+    fn overlaps(&self, start: VirtPageNum, end: VirtPageNum) -> bool {
+        self.vpn_range.get_start() < end && start < self.vpn_range.get_end()
+    }
+
+    // 逻辑段中的页是否在第一次被访问时才建立映射
+    fn is_lazy(&self) -> bool {
+        matches!(self.map_type, MapType::File { .. } | MapType::Lazy { .. })
+    }
+
+    // 逻辑段中私有的页是否可以被换出到交换区
+    fn is_swappable(&self) -> bool {
+        matches!(
+            self.map_type,
+            MapType::File { shared: false, .. } | MapType::Lazy { .. }
+        )
+    }
+
+    // vpn 对应的页在文件（或者共享内存段）中的偏移
+    fn file_offset(&self, vpn: VirtPageNum, offset: usize) -> usize {
+        offset + (vpn.0 - self.vpn_range.get_start().0) * PAGE_SIZE
+    }
+
+    // vpn 的页表项权限。共享的文件映射在第一次写入之前不给写权限，
+    // 这样就能通过写缺页知道哪些页被修改过，需要写回文件。
+    // 页缓存中的页总是只读的，写入时通过缺页复制。
+    fn pte_flags(&self, vpn: VirtPageNum) -> PTEFlags {
+        let mut flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        if let MapType::File { shared: true, .. } = self.map_type {
+            if !self.dirty_pages.contains(&vpn) {
+                flags.remove(PTEFlags::W);
+            }
+        }
+        if self.cached_pages.contains_key(&vpn) {
+            flags.remove(PTEFlags::W);
+        }
+        flags
+    }
+
     // map_one 为一个 vpn 申请一个物理页框，
     // 将 vpn 和 ppn 的映射关系保存到 page table 中。
-    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
-        let ppn: PhysPageNum;
-        match self.map_type {
-            MapType::Identical => ppn = PhysPageNum(vpn.0),
-            MapType::Framed => {
-                let frame = frame_alloc().unwrap();
-                ppn = frame.ppn;
-                self.data_frames.insert(vpn, frame);
+    // 没有空闲的页框时返回 false，vpn 保持没有映射。
+    #[must_use]
+    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        let ppn = match &self.map_type {
+            MapType::Identical => PhysPageNum(vpn.0),
+            MapType::Shared { shm, offset } => shm.ppn(self.file_offset(vpn, *offset) / PAGE_SIZE),
+            MapType::Framed | MapType::File { .. } | MapType::Lazy { .. } => {
+                return match frame_alloc() {
+                    Some(frame) => self.map_frame(page_table, vpn, Arc::new(frame)),
+                    None => false,
+                };
             }
+        };
+        page_table.map(vpn, ppn, self.pte_flags(vpn))
+    }
+
+    // 把逻辑段自己的页框 frame 映射到 vpn，没有空闲的页框用来创建页表时返回 false
+    #[must_use]
+    fn map_frame(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+        frame: Arc<FrameTracker>,
+    ) -> bool {
+        if !page_table.map(vpn, frame.ppn, self.pte_flags(vpn)) {
+            return false;
         }
+        self.data_frames.insert(vpn, frame);
+        true
+    }
 
-        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
-        page_table.map(vpn, ppn, pte_flags);
+    // 把页缓存中的页 frame 只读地映射到 vpn，没有空闲的页框用来创建页表时返回 false
+    #[must_use]
+    fn map_cached(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+        frame: Arc<FrameTracker>,
+    ) -> bool {
+        let ppn = frame.ppn;
+        self.cached_pages.insert(vpn, frame);
+        if !page_table.map(vpn, ppn, self.pte_flags(vpn)) {
+            self.cached_pages.remove(&vpn);
+            return false;
+        }
+        true
     }
 
     #[allow(unused)]
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
-        if self.map_type == MapType::Framed {
-            self.data_frames.remove(&vpn);
+        match self.map_type {
+            MapType::Identical | MapType::Shared { .. } => {}
+            MapType::Framed => {
+                self.data_frames.remove(&vpn);
+            }
+            MapType::File { .. } => {
+                if let Some(slot) = self.swapped.remove(&vpn) {
+                    swap::swap_free(slot);
+                }
+                if self.cached_pages.remove(&vpn).is_none() {
+                    // 没有被访问过和被换出的页不在页表中
+                    if !self.data_frames.contains_key(&vpn) {
+                        return;
+                    }
+                    self.write_back(vpn);
+                    self.data_frames.remove(&vpn);
+                }
+            }
+            MapType::Lazy { .. } => {
+                if let Some(slot) = self.swapped.remove(&vpn) {
+                    swap::swap_free(slot);
+                }
+                // 没有被访问过和被换出的页不在页表中
+                if self.data_frames.remove(&vpn).is_none() {
+                    return;
+                }
+            }
         }
         page_table.unmap(vpn);
     }
 
-    // map 将逻辑段包含的所有 vpn 与 ppn 的映射关系保存到 page table 中
-    pub fn map(&mut self, page_table: &mut PageTable) {
-        for vpn in self.vpn_range {
-            self.map_one(page_table, vpn);
+    // 修改逻辑段的权限，已经映射的页的页表项随之更新
+    pub fn set_permission(&mut self, page_table: &mut PageTable, permission: MapPermission) {
+        self.map_perm = permission;
+        let vpns: Vec<VirtPageNum> = if self.is_lazy() {
+            self.data_frames
+                .keys()
+                .chain(self.cached_pages.keys())
+                .copied()
+                .collect()
+        } else {
+            self.vpn_range.into_iter().collect()
+        };
+        for vpn in vpns {
+            page_table.set_flags(vpn, self.pte_flags(vpn));
+        }
+    }
+
+    // map 将逻辑段包含的所有 vpn 与 ppn 的映射关系保存到 page table 中，
+    // 文件映射和惰性的逻辑段在缺页时才逐页建立映射。
+    // 没有空闲的页框时解除已经建立的映射并返回 false。
+    #[must_use]
+    pub fn map(&mut self, page_table: &mut PageTable) -> bool {
+        if self.is_lazy() {
+            return true;
         }
+        self.map_range(page_table, self.vpn_range)
+    }
+
+    // 逐页映射 vpns，失败时解除其中已经建立的映射
+    fn map_range(&mut self, page_table: &mut PageTable, vpns: VPNRange) -> bool {
+        for vpn in vpns {
+            if !self.map_one(page_table, vpn) {
+                for mapped in VPNRange::new(vpns.get_start(), vpn) {
+                    self.unmap_one(page_table, mapped);
+                }
+                return false;
+            }
+        }
+        true
     }
 
     #[allow(unused)]
@@ -126,10 +398,40 @@ impl MapArea {
         }
     }
 
+    // 把逻辑段的结束位置调整为 new_end，多出来的页被映射（惰性的逻辑段除外），
+    // 去掉的页被解除映射。没有空闲的页框时返回 false，逻辑段保持不变。
+    #[must_use]
+    pub fn resize(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) -> bool {
+        let start = self.vpn_range.get_start();
+        let end = self.vpn_range.get_end();
+        assert!(start <= new_end, "resize {:?} out of area", new_end);
+        if new_end < end {
+            for vpn in VPNRange::new(new_end, end) {
+                self.unmap_one(page_table, vpn);
+            }
+        } else if !self.is_lazy() && !self.map_range(page_table, VPNRange::new(end, new_end)) {
+            return false;
+        }
+        self.vpn_range = VPNRange::new(start, new_end);
+        true
+    }
+
+    // 把初始化为 0 的惰性逻辑段的起始位置向下扩展到 new_start，比如自动增长的用户栈。
+    // 新增的页同样在第一次被访问时才分配。
+    pub fn grow_down(&mut self, new_start: VirtPageNum) {
+        assert!(
+            matches!(self.map_type, MapType::Lazy { data: None, .. }),
+            "only zeroed areas can grow down"
+        );
+        let end = self.vpn_range.get_end();
+        assert!(new_start <= self.vpn_range.get_start());
+        self.vpn_range = VPNRange::new(new_start, end);
+    }
+
     // copy_data 将 data 的数据拷贝到当前逻辑段中对应的物理内存中。
     // 需要注意的是 data 长度不能超过当前逻辑段的长度，按页为单位拷贝。
     pub fn copy_data(&mut self, page_table: &mut PageTable, data: &[u8]) {
-        assert_eq!(self.map_type, MapType::Framed);
+        assert!(matches!(self.map_type, MapType::Framed));
         let mut start = 0;
         let mut current_vpn = self.vpn_range.get_start();
         let len = data.len();
@@ -148,38 +450,285 @@ impl MapArea {
             current_vpn.step();
         }
     }
+
+    // 处理逻辑段中 vpn 的缺页，access 为这次访问需要的权限（R、W 或 X）。
+    pub fn handle_page_fault(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+        access: MapPermission,
+    ) -> Result<(), PageFaultError> {
+        if !self.map_perm.contains(access) {
+            return Err(PageFaultError::Illegal);
+        }
+        self.fault(page_table, vpn, access.contains(MapPermission::W))
+    }
+
+    // 建立 vpn 的映射，write 表示这次访问是写入。
+    // 共享的文件映射第一次访问时从文件中读取整页，第一次写入时把页记为脏页并打开
+    // 写权限；私有的文件映射读取时映射页缓存中的页，写入时复制一份；惰性的逻辑段
+    // 第一次访问时分配页框并拷贝初始内容。被换出的页从交换区读回。
+    // 没有空闲的页框时 vpn 保持原来的状态，之后可以重新处理这次缺页。
+    fn fault(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+        write: bool,
+    ) -> Result<(), PageFaultError> {
+        if let Some(&slot) = self.swapped.get(&vpn) {
+            if !self.map_one(page_table, vpn) {
+                return Err(PageFaultError::OutOfMemory);
+            }
+            self.swapped.remove(&vpn);
+            swap::swap_in(slot, self.data_frames[&vpn].ppn);
+            return Ok(());
+        }
+        match self.map_type.clone() {
+            MapType::File {
+                inode,
+                offset,
+                shared: true,
+            } => {
+                if !self.data_frames.contains_key(&vpn) {
+                    if !self.map_one(page_table, vpn) {
+                        return Err(PageFaultError::OutOfMemory);
+                    }
+                    // 超出文件末尾的部分保持为 0
+                    let file_offset = self.file_offset(vpn, offset);
+                    inode.read_at(file_offset, self.data_frames[&vpn].ppn.get_bytes_array());
+                }
+                if write {
+                    self.dirty_pages.insert(vpn);
+                    page_table.set_flags(vpn, self.pte_flags(vpn));
+                }
+            }
+            MapType::File {
+                inode,
+                offset,
+                shared: false,
+            } => {
+                // 已经复制过的页有逻辑段的全部权限，不会再缺页
+                if self.data_frames.contains_key(&vpn)
+                    || (!write && self.cached_pages.contains_key(&vpn))
+                {
+                    return Err(PageFaultError::Illegal);
+                }
+                let page = match self.cached_pages.get(&vpn) {
+                    Some(page) => page.clone(),
+                    None => page_cache::get_page(&inode, self.file_offset(vpn, offset))
+                        .ok_or(PageFaultError::OutOfMemory)?,
+                };
+                if !write {
+                    if !self.map_cached(page_table, vpn, page) {
+                        return Err(PageFaultError::OutOfMemory);
+                    }
+                    return Ok(());
+                }
+                // 先复制出私有的页，再替换掉只读的页缓存中的页
+                let frame = frame_alloc().ok_or(PageFaultError::OutOfMemory)?;
+                frame
+                    .ppn
+                    .get_bytes_array()
+                    .copy_from_slice(page.ppn.get_bytes_array());
+                if self.cached_pages.remove(&vpn).is_some() {
+                    page_table.unmap(vpn);
+                }
+                if !self.map_frame(page_table, vpn, Arc::new(frame)) {
+                    return Err(PageFaultError::OutOfMemory);
+                }
+            }
+            MapType::Lazy { data, offset, len } => {
+                // 已经映射的页有逻辑段的全部权限，不会再缺页
+                if self.data_frames.contains_key(&vpn) {
+                    return Err(PageFaultError::Illegal);
+                }
+                let frame = frame_alloc().ok_or(PageFaultError::OutOfMemory)?;
+                // 超出 len 的部分（比如 .bss）保持为 0
+                let start = (vpn.0 - self.vpn_range.get_start().0) * PAGE_SIZE;
+                if let Some(inode) = data.filter(|_| start < len) {
+                    let size = (len - start).min(PAGE_SIZE);
+                    inode.read_at(offset + start, &mut frame.ppn.get_bytes_array()[..size]);
+                }
+                if !self.map_frame(page_table, vpn, Arc::new(frame)) {
+                    return Err(PageFaultError::OutOfMemory);
+                }
+            }
+            _ => return Err(PageFaultError::Illegal),
+        }
+        Ok(())
+    }
+
+    // 把 vpn 对应的页换出到交换区并释放页框，交换区已满时返回 false
+    fn swap_out(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        let slot = match swap::swap_out(self.data_frames[&vpn].ppn) {
+            Some(slot) => slot,
+            None => return false,
+        };
+        self.swapped.insert(vpn, slot);
+        self.data_frames.remove(&vpn);
+        page_table.unmap(vpn);
+        true
+    }
+
+    // 把 vpn 对应的脏页写回文件，返回这一页是否是脏页。
+    // 写回时不会改变文件的大小，文件末尾之后的部分被丢弃。
+    fn write_back(&mut self, vpn: VirtPageNum) -> bool {
+        if !self.dirty_pages.remove(&vpn) {
+            return false;
+        }
+        if let MapType::File { inode, offset, .. } = &self.map_type {
+            let file_offset = self.file_offset(vpn, *offset);
+            let size = inode.size();
+            if file_offset < size {
+                let len = (size - file_offset).min(PAGE_SIZE);
+                let data = &self.data_frames[&vpn].ppn.get_bytes_array()[..len];
+                inode.write_at(file_offset, data);
+                page_cache::invalidate(inode, file_offset, len);
+            }
+        }
+        true
+    }
+
+    // 把 [start, end) 中的脏页写回文件，并重新去掉写权限以便记录之后的修改
+    pub fn sync(&mut self, page_table: &mut PageTable, start: VirtPageNum, end: VirtPageNum) {
+        let dirty_pages: Vec<VirtPageNum> = self.dirty_pages.range(start..end).copied().collect();
+        for vpn in dirty_pages {
+            self.write_back(vpn);
+            page_table.set_flags(vpn, self.pte_flags(vpn));
+        }
+    }
+}
+
+// 逻辑段被回收时（munmap、exec 和进程退出）把还没有写回的脏页写回文件，
+// 并释放被换出的页占用的 slot
+impl Drop for MapArea {
+    fn drop(&mut self) {
+        let dirty_pages: Vec<VirtPageNum> = self.dirty_pages.iter().copied().collect();
+        for vpn in dirty_pages {
+            self.write_back(vpn);
+        }
+        for slot in self.swapped.values() {
+            swap::swap_free(*slot);
+        }
+    }
 }
 
 pub struct MemorySet {
     page_table: PageTable,
     areas: Vec<MapArea>,
+    // 用户堆的起始地址（按页对齐）和当前的 program break，
+    // 用户堆逻辑段覆盖 [heap_bottom, brk) 所在的页
+    heap_bottom: usize,
+    brk: usize,
+    // 用户栈的栈顶，以及 mmap 和 shmat 在没有指定地址时开始寻找空闲区域的位置
+    stack_top: usize,
+    mmap_base: usize,
+    // 内核正在直接访问的用户缓冲区的数量（比如阻塞的 read），大于 0 时不能换出页
+    pinned: usize,
 }
 
 impl MemorySet {
-    fn new_bare() -> Self {
-        Self {
-            page_table: PageTable::new(),
+    // 没有空闲的页框用来创建根页表时返回 None
+    fn new_bare() -> Option<Self> {
+        Some(Self {
+            page_table: PageTable::new()?,
             areas: Vec::new(),
-        }
+            heap_bottom: 0,
+            brk: 0,
+            stack_top: USER_STACK_TOP,
+            mmap_base: MMAP_BASE,
+            pinned: 0,
+        })
+    }
+
+    // 地址空间中已经映射了页框的页数，包括与其他进程共享的页缓存中的页
+    pub fn resident_pages(&self) -> usize {
+        self.areas
+            .iter()
+            .map(|area| area.data_frames.len() + area.cached_pages.len())
+            .sum()
     }
 
     pub fn token(&self) -> usize {
         self.page_table.token()
     }
 
-    // insert_framed_area 将逻辑地址映射到 memory set 中。
+    // insert_framed_area 将逻辑地址映射到 memory set 中，没有空闲的页框时返回 false。
+    #[must_use]
     pub fn insert_framed_area(
         &mut self,
         start_va: VirtAddr,
         end_va: VirtAddr,
         permission: MapPermission,
+    ) -> bool {
+        self.try_push(
+            MapArea::new(start_va, end_va, MapType::Framed, permission),
+            None,
+        )
+    }
+
+    // 插入一段初始化为 0 的匿名内存，页框在第一次被访问时才分配
+    pub fn insert_lazy_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
     ) {
         self.push(
-            MapArea::new(start_va, end_va, MapType::Framed, permission),
+            MapArea::new(start_va, end_va, MapType::zeroed(), permission),
             None,
         );
     }
 
+    // 按照 MapType::File 描述的文件映射 [start_va, end_va)，
+    // 页在第一次被访问时才从文件中读取。mprotect 不能把权限修改为超出 max_permission。
+    pub fn insert_file_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+        max_permission: MapPermission,
+        map_type: MapType,
+    ) {
+        assert!(matches!(map_type, MapType::File { .. }));
+        let mut map_area = MapArea::new(start_va, end_va, map_type, permission);
+        map_area.max_perm = max_permission;
+        self.push(map_area, None);
+    }
+
+    // 把共享内存段 shm 映射到 [start_va, end_va)，没有空闲的页框用来创建页表时返回 false。
+    // mprotect 不能把权限修改为超出 max_permission。
+    #[must_use]
+    pub fn insert_shared_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+        max_permission: MapPermission,
+        shm: Arc<SharedMemory>,
+    ) -> bool {
+        let mut map_area = MapArea::new(
+            start_va,
+            end_va,
+            MapType::Shared { shm, offset: 0 },
+            permission,
+        );
+        map_area.max_perm = max_permission;
+        self.try_push(map_area, None)
+    }
+
+    // 解除从 start_vpn 开始的共享内存映射，start_vpn 处没有共享内存映射时返回 false
+    pub fn remove_shared_area(&mut self, start_vpn: VirtPageNum) -> bool {
+        let shared = self.areas.iter().any(|area| {
+            area.vpn_range.get_start() == start_vpn
+                && matches!(area.map_type, MapType::Shared { .. })
+        });
+        if shared {
+            self.remove_area_with_start_vpn(start_vpn);
+        }
+        shared
+    }
+
     // 从 memory_set 中移除一个指定的 map_area
     pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
         if let Some((idx, area)) = self
@@ -193,25 +742,437 @@ impl MemorySet {
         }
     }
 
+    // [start, end) 是否没有被任何逻辑段占用
+    pub fn is_free(&self, start: VirtPageNum, end: VirtPageNum) -> bool {
+        !self.areas.iter().any(|area| area.overlaps(start, end))
+    }
+
+    // 在 [lower, upper) 中寻找第一段长度为 page_count 且没有被占用的虚拟页
+    pub fn find_free_range(
+        &self,
+        lower: VirtPageNum,
+        upper: VirtPageNum,
+        page_count: usize,
+    ) -> Option<VirtPageNum> {
+        let mut start = lower;
+        while start.0 + page_count <= upper.0 {
+            let end = VirtPageNum(start.0 + page_count);
+            match self
+                .areas
+                .iter()
+                .filter(|area| area.overlaps(start, end))
+                .map(|area| area.vpn_range.get_end())
+                .max()
+            {
+                // 跳过与之重叠的逻辑段继续寻找
+                Some(area_end) => start = area_end,
+                None => return Some(start),
+            }
+        }
+        None
+    }
+
+    // 解除 [start, end) 的映射。与之部分重叠的逻辑段会被拆分，只移除重叠的部分。
+    pub fn remove_range(&mut self, start: VirtPageNum, end: VirtPageNum) {
+        let mut idx = 0;
+        while idx < self.areas.len() {
+            let area = &mut self.areas[idx];
+            if !area.overlaps(start, end) {
+                idx += 1;
+                continue;
+            }
+            // 拆出 [start, end) 之外的部分，留在 areas 中
+            if area.vpn_range.get_end() > end {
+                let tail = area.split_off(end);
+                self.areas.push(tail);
+            }
+            let area = &mut self.areas[idx];
+            if area.vpn_range.get_start() < start {
+                let middle = area.split_off(start);
+                self.areas.push(middle);
+                idx += 1;
+                continue;
+            }
+            let start_vpn = area.vpn_range.get_start();
+            self.remove_area_with_start_vpn(start_vpn);
+        }
+    }
+
+    pub fn brk(&self) -> usize {
+        self.brk
+    }
+
+    pub fn mmap_base(&self) -> usize {
+        self.mmap_base
+    }
+
+    // 把 program break 调整为 new_brk，用户堆随之增长或者缩小。
+    // new_brk 低于堆的起始地址、堆会与其他逻辑段重叠或者没有空闲的页框时返回 false。
+    pub fn set_brk(&mut self, new_brk: usize) -> bool {
+        if new_brk < self.heap_bottom || new_brk >= self.stack_top {
+            return false;
+        }
+        let old_end = VirtAddr::from(self.brk).ceil();
+        let new_end = VirtAddr::from(new_brk).ceil();
+        // 堆与用户栈之间至少留出一个 guard page
+        if new_end > old_end && !self.is_free(old_end, VirtPageNum(new_end.0 + 1)) {
+            return false;
+        }
+        // mprotect 可能把堆拆分成了几个逻辑段，从最上面的一段开始调整，
+        // 缩小时完全位于 new_end 之上的逻辑段被移除，只有最下面的一段可以为空
+        let heap_start = VirtAddr::from(self.heap_bottom).floor();
+        let mut end = old_end;
+        loop {
+            let idx = self
+                .areas
+                .iter()
+                .position(|area| {
+                    area.vpn_range.get_end() == end && area.vpn_range.get_start() >= heap_start
+                })
+                .unwrap();
+            let start = self.areas[idx].vpn_range.get_start();
+            if start < new_end || start == heap_start {
+                if !self.areas[idx].resize(&mut self.page_table, new_end) {
+                    return false;
+                }
+                break;
+            }
+            self.remove_area_with_start_vpn(start);
+            end = start;
+        }
+        self.brk = new_brk;
+        true
+    }
+
+    // 在 vpn 处拆分跨越 vpn 的逻辑段
+    fn split_at(&mut self, vpn: VirtPageNum) {
+        if let Some(area) = self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.get_start() < vpn && vpn < area.vpn_range.get_end())
+        {
+            let tail = area.split_off(vpn);
+            self.areas.push(tail);
+        }
+    }
+
+    // 把 [start, end) 的权限修改为 permission，与之部分重叠的逻辑段会被拆分，
+    // 已经映射的页的页表项随之更新并刷新 TLB。[start, end) 中有没有被映射的页
+    // 或者用户不能访问的逻辑段时返回 -ENOMEM，permission 超出了某个逻辑段允许的
+    // 最大权限时返回 -EACCES，权限保持不变。
+    pub fn protect(
+        &mut self,
+        start: VirtPageNum,
+        end: VirtPageNum,
+        permission: MapPermission,
+    ) -> Result<(), isize> {
+        let mut covered = 0;
+        let mut allowed = true;
+        for area in self.areas.iter().filter(|area| area.overlaps(start, end)) {
+            if !area.map_perm.contains(MapPermission::U) {
+                return Err(-ENOMEM);
+            }
+            allowed &= area.max_perm.contains(permission);
+            covered +=
+                area.vpn_range.get_end().0.min(end.0) - area.vpn_range.get_start().0.max(start.0);
+        }
+        if covered != end.0 - start.0 {
+            return Err(-ENOMEM);
+        }
+        if !allowed {
+            return Err(-EACCES);
+        }
+        self.split_at(start);
+        self.split_at(end);
+        for area in self.areas.iter_mut() {
+            if area.overlaps(start, end) {
+                area.set_permission(&mut self.page_table, permission);
+            }
+        }
+        unsafe {
+            asm!("sfence.vma");
+        }
+        Ok(())
+    }
+
+    // 把 [start, end) 中共享文件映射的脏页写回文件
+    pub fn sync_range(&mut self, start: VirtPageNum, end: VirtPageNum) {
+        for area in self.areas.iter_mut() {
+            if area.overlaps(start, end) {
+                area.sync(&mut self.page_table, start, end);
+            }
+        }
+    }
+
+    // 处理用户程序访问 va 时发生的缺页，access 为这次访问需要的权限。
+    // va 没有被映射或者权限不足时返回 PageFaultError::Illegal。
+    pub fn handle_page_fault(
+        &mut self,
+        va: VirtAddr,
+        access: MapPermission,
+    ) -> Result<(), PageFaultError> {
+        let vpn = va.floor();
+        if self.is_free(vpn, VirtPageNum(vpn.0 + 1)) && !self.grow_stack(vpn) {
+            return Err(PageFaultError::Illegal);
+        }
+        let page_table = &mut self.page_table;
+        match self
+            .areas
+            .iter_mut()
+            .find(|area| area.overlaps(vpn, VirtPageNum(vpn.0 + 1)))
+        {
+            Some(area) => area.handle_page_fault(page_table, vpn, access),
+            None => Err(PageFaultError::Illegal),
+        }
+    }
+
+    // 访问用户栈之下的 vpn 时把用户栈向下扩展到 vpn。
+    // 用户栈的大小不能超过 USER_STACK_LIMIT，并且与下面的逻辑段之间至少隔着一个 guard page，
+    // 否则返回 false，这次访问是一次真正的栈溢出或者非法访问。
+    fn grow_stack(&mut self, vpn: VirtPageNum) -> bool {
+        let stack_top = VirtAddr::from(self.stack_top).floor();
+        let stack_limit = VirtAddr::from(self.stack_top - USER_STACK_LIMIT).floor();
+        // mprotect 可能把用户栈拆分成了几个相邻的逻辑段，向下扩展最下面的一段
+        let mut stack = None;
+        let mut stack_bottom = stack_top;
+        while let Some(idx) = self.areas.iter().position(|area| {
+            area.vpn_range.get_end() == stack_bottom && area.vpn_range.get_start() < stack_bottom
+        }) {
+            stack = Some(idx);
+            stack_bottom = self.areas[idx].vpn_range.get_start();
+        }
+        let stack = match stack {
+            Some(idx) => idx,
+            None => return false,
+        };
+        if vpn < stack_limit
+            || vpn >= stack_bottom
+            || !self.is_free(VirtPageNum(vpn.0 - 1), stack_bottom)
+        {
+            return false;
+        }
+        self.areas[stack].grow_down(vpn);
+        true
+    }
+
+    // vpn 已经映射，并且 access 为 W 时已经可写，内核可以直接访问
+    fn is_present(&self, vpn: VirtPageNum, access: MapPermission) -> bool {
+        self.translate(vpn).map_or(false, |pte| {
+            pte.is_valid() && (!access.contains(MapPermission::W) || pte.writable())
+        })
+    }
+
+    // 检查用户缓冲区 [start, start + len) 是否都在用户可以按照 access 访问的逻辑段中
+    // （或者在用户栈还可以向下扩展的范围内），返回其中还需要处理缺页的页数。
+    // 缓冲区不合法时返回 None。
+    pub fn missing_pages(&self, start: usize, len: usize, access: MapPermission) -> Option<usize> {
+        let end = start.checked_add(len)?;
+        // 超出 SV39 地址空间的地址会被 VirtAddr 截断
+        if VirtAddr::from(end).0 != end {
+            return None;
+        }
+        let stack_limit = VirtAddr::from(self.stack_top - USER_STACK_LIMIT).floor();
+        let stack_top = VirtAddr::from(self.stack_top).floor();
+        let mut missing = 0;
+        let mut vpn = VirtAddr::from(start).floor();
+        let end_vpn = VirtAddr::from(end).ceil();
+        while vpn < end_vpn {
+            match self
+                .areas
+                .iter()
+                .find(|area| area.overlaps(vpn, VirtPageNum(vpn.0 + 1)))
+            {
+                Some(area) if area.map_perm.contains(access | MapPermission::U) => {
+                    if !self.is_present(vpn, access) {
+                        missing += 1;
+                    }
+                }
+                None if vpn >= stack_limit && vpn < stack_top => missing += 1,
+                _ => return None,
+            }
+            vpn.step();
+        }
+        Some(missing)
+    }
+
+    // 内核访问用户缓冲区 [start, start + len) 之前先处理其中的缺页，
+    // access 为 W 时要求缓冲区可写。缓冲区不合法时返回 Illegal，没有空闲的页框时
+    // 返回 OutOfMemory。
+    pub fn populate(
+        &mut self,
+        start: usize,
+        len: usize,
+        access: MapPermission,
+    ) -> Result<(), PageFaultError> {
+        let end = start.checked_add(len).ok_or(PageFaultError::Illegal)?;
+        let mut vpn = VirtAddr::from(start).floor();
+        let end_vpn = VirtAddr::from(end).ceil();
+        while vpn < end_vpn {
+            if !self.is_present(vpn, access) {
+                self.handle_page_fault(vpn.into(), access)?;
+            }
+            vpn.step();
+        }
+        Ok(())
+    }
+
+    pub fn pin(&mut self) {
+        self.pinned += 1;
+    }
+
+    pub fn unpin(&mut self) {
+        self.pinned -= 1;
+    }
+
+    // 用 clock 算法从 start 开始按照地址顺序检查惰性逻辑段中的页：最近被访问过的页
+    // 清除访问位后跳过，否则换出到交换区。返回换出的页数，换出 count 个页后还有
+    // 没有检查的页时同时返回下一次开始检查的位置。
+    pub fn swap_out_pages(
+        &mut self,
+        start: VirtPageNum,
+        count: usize,
+    ) -> (usize, Option<VirtPageNum>) {
+        if self.pinned > 0 {
+            return (0, None);
+        }
+        let mut areas: Vec<&mut MapArea> = self
+            .areas
+            .iter_mut()
+            .filter(|area| area.is_swappable() && area.vpn_range.get_end() > start)
+            .collect();
+        areas.sort_by_key(|area| area.vpn_range.get_start());
+        let mut swapped = 0;
+        for area in areas {
+            let vpns: Vec<VirtPageNum> = area
+                .data_frames
+                .range(start..)
+                .map(|(vpn, _)| *vpn)
+                .collect();
+            for vpn in vpns {
+                if swapped == count {
+                    return (swapped, Some(vpn));
+                }
+                if self.page_table.test_and_clear_accessed(vpn) {
+                    continue;
+                }
+                if !area.swap_out(&mut self.page_table, vpn) {
+                    return (swapped, None);
+                }
+                swapped += 1;
+            }
+        }
+        (swapped, None)
+    }
+
+    // 加载程序时内核写入 va 处的一个 usize（比如重定位），不检查逻辑段的权限。
+    // va 所在的页先被复制成私有的页，不会修改页缓存中的页。va 需要按 usize 对齐。
+    fn write_on_load(&mut self, va: usize, value: usize) -> Result<(), PageFaultError> {
+        let vpn = VirtAddr::from(va).floor();
+        let page_table = &mut self.page_table;
+        let area = match self
+            .areas
+            .iter_mut()
+            .find(|area| area.overlaps(vpn, VirtPageNum(vpn.0 + 1)))
+        {
+            Some(area) => area,
+            None => return Err(PageFaultError::Illegal),
+        };
+        let present = page_table
+            .translate(vpn)
+            .map_or(false, |pte| pte.is_valid());
+        if !present || area.cached_pages.contains_key(&vpn) {
+            area.fault(page_table, vpn, true)?;
+        }
+        *page_table::translated_ref_mut(self.token(), va as *mut usize) = value;
+        Ok(())
+    }
+
+    // 处理以 0 结尾的用户字符串 ptr 所在各页的缺页，返回 false 表示字符串不合法
+    pub fn populate_str(&mut self, ptr: usize) -> bool {
+        let mut va = VirtAddr::from(ptr);
+        loop {
+            if self.populate(va.into(), 1, MapPermission::R).is_err() {
+                return false;
+            }
+            let mut vpn = va.floor();
+            let bytes = &self.translate(vpn).unwrap().ppn().get_bytes_array()[va.page_offset()..];
+            if bytes.contains(&0) {
+                return true;
+            }
+            vpn.step();
+            va = vpn.into();
+        }
+    }
+
+    // 把 value 拷贝到用户地址空间的 ptr 处，拷贝之前先处理其中的缺页。
+    // 目标地址没有映射为用户可写时返回 false。
+    pub fn copy_to_user<T: Copy>(&mut self, ptr: *mut T, value: &T) -> bool {
+        self.populate(ptr as usize, size_of::<T>(), MapPermission::W)
+            .is_ok()
+            && page_table::copy_to_user(self.token(), ptr, value)
+    }
+
+    // 从用户地址空间的 ptr 处读取一个 T，读取之前先处理其中的缺页。
+    // 源地址没有映射为用户可访问时返回 None。
+    pub fn copy_from_user<T: Copy>(&mut self, ptr: *const T) -> Option<T> {
+        if self
+            .populate(ptr as usize, size_of::<T>(), MapPermission::R)
+            .is_err()
+        {
+            return None;
+        }
+        page_table::copy_from_user(self.token(), ptr)
+    }
+
     // push 将逻辑段内容映射到物理内存中，如果有数据则深拷贝数据，最后将 map_area 保存到 mmset 中。
-    fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) {
-        map_area.map(&mut self.page_table);
+    // 没有空闲的页框时丢弃 map_area 并返回 false。
+    #[must_use]
+    fn try_push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) -> bool {
+        if !map_area.map(&mut self.page_table) {
+            return false;
+        }
         if let Some(data) = data {
             map_area.copy_data(&mut self.page_table, data);
         }
         self.areas.push(map_area);
+        true
+    }
+
+    // 与 try_push 相同，用于一定能够映射成功的逻辑段：内核启动时的逻辑段，
+    // 以及在缺页时才分配页框的惰性逻辑段
+    fn push(&mut self, map_area: MapArea, data: Option<&[u8]>) {
+        assert!(self.try_push(map_area, data), "out of memory");
     }
 
-    fn map_trampoline(&mut self) {
+    #[must_use]
+    fn map_trampoline(&mut self) -> bool {
         let vpn: VirtPageNum = VirtAddr::from(TRAMPOLINE).into();
         let ppn: PhysPageNum = PhysAddr::from(strampoline as usize).into();
-        self.page_table.map(vpn, ppn, PTEFlags::R | PTEFlags::X);
+        self.page_table.map(vpn, ppn, PTEFlags::R | PTEFlags::X)
+    }
+
+    // sigreturn 跳板需要被用户态执行，所以只映射到用户地址空间中
+    #[must_use]
+    fn map_sigreturn_trampoline(&mut self) -> bool {
+        let vpn: VirtPageNum = VirtAddr::from(SIGRETURN_TRAMPOLINE).into();
+        let ppn: PhysPageNum = PhysAddr::from(ssigreturn as usize).into();
+        self.page_table
+            .map(vpn, ppn, PTEFlags::R | PTEFlags::X | PTEFlags::U)
+    }
+
+    // 新的用户地址空间，只映射了两个跳板
+    fn new_user() -> Option<Self> {
+        let mut memory_set = Self::new_bare()?;
+        if !memory_set.map_trampoline() || !memory_set.map_sigreturn_trampoline() {
+            return None;
+        }
+        Some(memory_set)
     }
 
     pub fn new_kernel() -> Self {
-        let mut memory_set = Self::new_bare();
+        let mut memory_set = Self::new_bare().expect("out of memory");
         // high kernel address space
-        memory_set.map_trampoline();
+        assert!(memory_set.map_trampoline(), "out of memory");
 
         // low kernel address space
         println!(".text [{:#x}, {:#x})", stext as usize, etext as usize);
@@ -261,16 +1222,16 @@ impl MemorySet {
         println!("mapping physical memory");
         let phy_mem_map_area = MapArea::new(
             (ekernel as usize).into(),
-            MEMORY_END.into(),
+            fdt::memory_end().into(),
             MapType::Identical,
             MapPermission::R | MapPermission::W,
         );
         memory_set.push(phy_mem_map_area, None);
 
         println!("mapping memory-mapped registers");
-        for (started_address, length) in config::MMIO {
-            let start_va: VirtAddr = (*started_address).into();
-            let end_va: VirtAddr = (*started_address + *length).into();
+        for (started_address, length) in fdt::mmio_regions() {
+            let start_va: VirtAddr = started_address.into();
+            let end_va: VirtAddr = (started_address + length).into();
             memory_set.push(
                 MapArea::new(
                     start_va,
@@ -287,31 +1248,54 @@ impl MemorySet {
         memory_set
     }
 
-    // from_elf 根据 elf 文件创建一个 mmset，
+    // from_elf 根据 elf 文件 inode 创建一个 mmset，
     // 完成的事情包括验证 elf 文件是否合法，根据 program headers 加载数据的逻辑段，
-    // 设置 user stack，以及设置 trap context 地址。
+    // 设置 user heap（紧跟在最后一个段之后）和 user stack，以及设置 trap context 地址。
+    // randomize 为 true 并且没有通过启动参数关闭时，用户栈、堆和 mmap 区域的位置
+    // 随机偏移若干页 (ASLR)。
+    // 位置无关的可执行文件 (ET_DYN) 被加载到 ELF_ET_DYN_BASE 之上，并应用其中的
+    // R_RISCV_RELATIVE 重定位。
     // returns:
     //  - memory_set
     //  - user stack 栈顶虚拟地址
     //  - app 入口地址
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
-        let mut memory_set = Self::new_bare();
-        memory_set.map_trampoline();
+    //  - 需要放在用户栈上的辅助向量，不包括结尾的 AT_NULL
+    // elf 文件中的长度和偏移都不可信，不合法时返回 LoadError::InvalidElf，
+    // 没有空闲的页框时返回 LoadError::OutOfMemory。
+    pub fn from_elf(
+        inode: Arc<Inode>,
+        randomize: bool,
+    ) -> Result<(Self, usize, usize, Vec<AuxHeader>), LoadError> {
+        let randomize = randomize && RANDOMIZE_VA_SPACE.load(Ordering::Relaxed);
+        let random_offset = |pages: usize| {
+            if randomize {
+                random::random_below(pages) * PAGE_SIZE
+            } else {
+                0
+            }
+        };
+        let mut memory_set = Self::new_user().ok_or(LoadError::OutOfMemory)?;
 
-        // read elf header
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
+        // read elf header，只读取 elf header 和 program headers
+        let headers = elf::read_headers(&inode).ok_or(LoadError::InvalidElf)?;
+        let elf = xmas_elf::ElfFile::new(&headers).map_err(|_| LoadError::InvalidElf)?;
         let elf_header = elf.header;
         let magic = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
+        if magic != [0x7f, 0x45, 0x4c, 0x46] {
+            return Err(LoadError::InvalidElf);
+        }
+        // ET_EXEC 按照链接地址加载，ET_DYN 的所有地址都加上加载基址
+        let load_base = match elf_header.pt2.type_().as_type() {
+            xmas_elf::header::Type::SharedObject => ELF_ET_DYN_BASE + random_offset(ASLR_PIE_PAGES),
+            _ => 0,
+        };
 
         // load program from program headers
         let ph_count = elf_header.pt2.ph_count();
         let mut max_end_vpn = VirtPageNum(0);
         for i in 0..ph_count {
-            let ph = elf.program_header(i).unwrap();
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
-                let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
-                let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
+            let ph = elf.program_header(i).map_err(|_| LoadError::InvalidElf)?;
+            if ph.get_type() == Ok(xmas_elf::program::Type::Load) {
                 let mut map_perm = MapPermission::U;
                 let ph_flags = ph.flags();
                 if ph_flags.is_read() {
@@ -323,28 +1307,112 @@ impl MemorySet {
                 if ph_flags.is_execute() {
                     map_perm |= MapPermission::X;
                 }
-                let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
-                max_end_vpn = map_area.vpn_range.get_end();
-                memory_set.push(
-                    map_area,
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
-                );
+                let (start, file_end, mem_end) = match load_base
+                    .checked_add(ph.virtual_addr() as usize)
+                    .and_then(|start| {
+                        let file_end = start.checked_add(ph.file_size() as usize)?;
+                        let mem_end = start.checked_add(ph.mem_size() as usize)?;
+                        Some((start, file_end, mem_end))
+                    }) {
+                    Some((start, file_end, mem_end)) if file_end <= mem_end => {
+                        (start, file_end, mem_end)
+                    }
+                    _ => return Err(LoadError::InvalidElf),
+                };
+                let offset = ph.offset() as usize;
+                if offset % PAGE_SIZE == start % PAGE_SIZE {
+                    // 完全由文件内容组成的页直接映射页缓存，运行同一个程序的进程共享这些页
+                    let page_start = start - start % PAGE_SIZE;
+                    let page_offset = offset - start % PAGE_SIZE;
+                    let file_page_end = (file_end - file_end % PAGE_SIZE).max(page_start);
+                    if file_page_end > page_start {
+                        let map_type = MapType::File {
+                            inode: inode.clone(),
+                            offset: page_offset,
+                            shared: false,
+                        };
+                        memory_set.push(
+                            MapArea::new(
+                                page_start.into(),
+                                file_page_end.into(),
+                                map_type,
+                                map_perm,
+                            ),
+                            None,
+                        );
+                    }
+                    // 剩下的部分（文件内容的最后一页和 .bss）在缺页时读取或者清零
+                    if mem_end > file_page_end {
+                        let map_type = MapType::Lazy {
+                            data: Some(inode.clone()),
+                            offset: page_offset + (file_page_end - page_start),
+                            len: file_end.saturating_sub(file_page_end),
+                        };
+                        memory_set.push(
+                            MapArea::new(file_page_end.into(), mem_end.into(), map_type, map_perm),
+                            None,
+                        );
+                    }
+                } else {
+                    // 文件偏移与地址没有按页对齐的段不能映射页缓存，数据在第一次被访问时读取
+                    let map_type = MapType::Lazy {
+                        data: Some(inode.clone()),
+                        offset,
+                        len: ph.file_size() as usize,
+                    };
+                    memory_set.push(
+                        MapArea::new(start.into(), mem_end.into(), map_type, map_perm),
+                        None,
+                    );
+                }
+                max_end_vpn = max_end_vpn.max(VirtAddr::from(mem_end).ceil());
+            }
+        }
+
+        // 重定位的目标所在的页先复制成私有的页，再写入加载后的地址。
+        // 只读段中的重定位同样直接写入页框。write_on_load 只处理 va 所在的一页，
+        // 没有按 usize 对齐的目标可能跨越两页，这样的程序不能被加载。
+        for relocation in elf::relocations(&elf, &inode).ok_or(LoadError::InvalidElf)? {
+            let va = load_base.wrapping_add(relocation.offset);
+            if va % size_of::<usize>() != 0 {
+                println!("[kernel] misaligned relocation at {:#x}", va);
+                return Err(LoadError::InvalidElf);
+            }
+            match memory_set.write_on_load(va, load_base.wrapping_add(relocation.addend)) {
+                Ok(()) => {}
+                Err(PageFaultError::Illegal) => {
+                    println!("[kernel] relocation at {:#x} is not mapped, ignored", va);
+                }
+                Err(PageFaultError::OutOfMemory) => return Err(LoadError::OutOfMemory),
             }
         }
 
-        let max_end_va: VirtAddr = max_end_vpn.into();
-        let mut user_stack_bottom: usize = max_end_va.into();
-        // guard page
-        user_stack_bottom += config::PAGE_SIZE;
+        // user heap，初始时为空，由 brk 调整大小
+        let heap_bottom_va: VirtAddr =
+            (usize::from(VirtAddr::from(max_end_vpn)) + random_offset(ASLR_HEAP_PAGES)).into();
+        memory_set.push(
+            MapArea::new(
+                heap_bottom_va,
+                heap_bottom_va,
+                MapType::zeroed(),
+                MapPermission::R | MapPermission::W | MapPermission::U,
+            ),
+            None,
+        );
+        memory_set.heap_bottom = heap_bottom_va.into();
+        memory_set.brk = memory_set.heap_bottom;
+        memory_set.mmap_base = MMAP_BASE + random_offset(ASLR_MMAP_PAGES);
 
-        // user stack
-        let user_stack_top = user_stack_bottom + config::USER_STACK_SIZE;
+        // user stack，初始大小为 USER_STACK_SIZE，缺页时自动向下增长
+        let user_stack_top = USER_STACK_TOP - random_offset(ASLR_STACK_PAGES);
+        memory_set.stack_top = user_stack_top;
+        let user_stack_bottom = user_stack_top - config::USER_STACK_SIZE;
         let user_stack_start_va = user_stack_bottom.into();
         let user_stack_end_va = user_stack_top.into();
         let user_stack_map_area = MapArea::new(
             user_stack_start_va,
             user_stack_end_va,
-            MapType::Framed,
+            MapType::zeroed(),
             MapPermission::R | MapPermission::W | MapPermission::U,
         );
         memory_set.push(user_stack_map_area, None);
@@ -356,33 +1424,92 @@ impl MemorySet {
             MapType::Framed,
             MapPermission::R | MapPermission::W,
         );
-        memory_set.push(trap_ctx_map_area, None);
+        if !memory_set.try_push(trap_ctx_map_area, None) {
+            return Err(LoadError::OutOfMemory);
+        }
 
-        (
-            memory_set,
-            user_stack_top,
-            elf.header.pt2.entry_point() as usize,
-        )
+        let entry_point = load_base.wrapping_add(elf_header.pt2.entry_point() as usize);
+        let mut auxv = vec![
+            AuxHeader::new(AT_PHENT, elf_header.pt2.ph_entry_size() as usize),
+            AuxHeader::new(AT_PHNUM, ph_count as usize),
+            AuxHeader::new(AT_PAGESZ, PAGE_SIZE),
+            AuxHeader::new(AT_BASE, load_base),
+            AuxHeader::new(AT_ENTRY, entry_point),
+        ];
+        if let Some(phdr) = elf::phdr_va(&elf) {
+            auxv.push(AuxHeader::new(AT_PHDR, load_base.wrapping_add(phdr)));
+        }
+
+        Ok((memory_set, user_stack_top, entry_point, auxv))
     }
 
-    //  创建并拷贝一个已有用户地址空间 (memory_set)
-    pub fn from_existed_user(user_space: &MemorySet) -> Self {
-        let mut memory_set = Self::new_bare();
-        memory_set.map_trampoline();
+    //  创建并拷贝一个已有用户地址空间 (memory_set)，没有空闲的页框或者交换区已满时返回 None
+    pub fn from_existed_user(user_space: &MemorySet) -> Option<Self> {
+        let mut memory_set = Self::new_user()?;
+        memory_set.heap_bottom = user_space.heap_bottom;
+        memory_set.brk = user_space.brk;
+        memory_set.stack_top = user_space.stack_top;
+        memory_set.mmap_base = user_space.mmap_base;
 
         for area in user_space.areas.iter() {
-            let new_map_area = MapArea::from_another(area);
-            memory_set.push(new_map_area, None);
-            for vpn in area.vpn_range {
-                let src_ppn = user_space.translate(vpn).unwrap().ppn();
-                let dst_ppn = memory_set.translate(vpn).unwrap().ppn();
-                dst_ppn
-                    .get_bytes_array()
-                    .copy_from_slice(src_ppn.get_bytes_array());
+            let mut new_map_area = MapArea::from_another(area);
+            let shared = matches!(area.map_type, MapType::File { shared: true, .. });
+            if area.is_lazy() {
+                // 文件映射和惰性的逻辑段只拷贝已经加载的页。
+                // 被换出的页在交换区中复制一份
+                for (vpn, slot) in area.swapped.iter() {
+                    match swap::swap_dup(*slot) {
+                        Some(new_slot) => new_map_area.swapped.insert(*vpn, new_slot),
+                        None => return None,
+                    };
+                }
+                for (vpn, src_frame) in area.data_frames.iter() {
+                    // 共享文件映射的页框与父进程共享，脏页由写过它的进程各自写回，
+                    // 子进程第一次写入时同样通过缺页记录脏页
+                    if shared {
+                        if !new_map_area.map_frame(
+                            &mut memory_set.page_table,
+                            *vpn,
+                            src_frame.clone(),
+                        ) {
+                            return None;
+                        }
+                        continue;
+                    }
+                    // 父进程的页此时不能被换出，空闲页框不够时直接把页写入子进程的交换区
+                    if area.is_swappable() && frame_free_count() <= RESERVED_FRAMES {
+                        if let Some(slot) = swap::swap_out(src_frame.ppn) {
+                            new_map_area.swapped.insert(*vpn, slot);
+                            continue;
+                        }
+                    }
+                    if !new_map_area.map_one(&mut memory_set.page_table, *vpn) {
+                        return None;
+                    }
+                }
+                // 页缓存中的页与父进程共享
+                for (vpn, frame) in area.cached_pages.iter() {
+                    if !new_map_area.map_cached(&mut memory_set.page_table, *vpn, frame.clone()) {
+                        return None;
+                    }
+                }
+            }
+            if !memory_set.try_push(new_map_area, None) {
+                return None;
+            }
+            if shared {
+                continue;
+            }
+            for (vpn, src_frame) in area.data_frames.iter() {
+                if let Some(pte) = memory_set.translate(*vpn).filter(|pte| pte.is_valid()) {
+                    pte.ppn()
+                        .get_bytes_array()
+                        .copy_from_slice(src_frame.ppn.get_bytes_array());
+                }
             }
         }
 
-        memory_set
+        Some(memory_set)
     }
 
     // activate 设置根页表地址并启用 SV39 分页
@@ -1,15 +1,28 @@
 pub mod address;
+pub mod elf;
 pub mod frame_allocator;
 mod heap_allocator;
 pub mod memory_set;
+pub mod page_cache;
 pub mod page_table;
+pub mod shm;
+mod swap;
 
 pub use frame_allocator::FrameTracker;
 pub use memory_set::KERNEL_SPACE;
 pub use page_table::UserBuffer;
 
-pub fn init() {
+// 内核堆不依赖设备树，需要在解析设备树之前初始化
+pub fn init_heap() {
     heap_allocator::init_heap();
+}
+
+pub fn init() {
     frame_allocator::init_frame_allocator();
     KERNEL_SPACE.exclusive_access().activate();
+    let stats = heap_allocator::heap_stats();
+    println!(
+        "[kernel] heap: {:#x} bytes, {:#x} allocated, {:#x} requested",
+        stats.total, stats.allocated, stats.requested
+    );
 }
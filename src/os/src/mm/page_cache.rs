@@ -0,0 +1,80 @@
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use easy_fs::Inode;
+use lazy_static::*;
+
+use crate::{config::PAGE_SIZE, sync::UPSafeCell};
+
+use super::frame_allocator::{frame_alloc, FrameTracker};
+
+// 页缓存：文件中按页对齐的内容，以 (inode id, 页号) 为索引。
+// 运行同一个程序的所有进程映射同样的页框，只读的段不再各自占用一份页框，
+// 可写的段在第一次写入时才复制 (copy-on-write)。
+// 页缓存和每个映射了它的逻辑段各持有一个引用，只被页缓存引用的页在
+// 内存不足时可以被丢弃。
+lazy_static! {
+    static ref PAGE_CACHE: UPSafeCell<BTreeMap<(usize, usize), Arc<FrameTracker>>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+// 返回 inode 中从 offset（按页对齐）开始的一页，不在页缓存中时从文件中读取，
+// 超出文件末尾的部分为 0。没有空闲的页框时返回 None。
+pub fn get_page(inode: &Inode, offset: usize) -> Option<Arc<FrameTracker>> {
+    assert_eq!(offset % PAGE_SIZE, 0);
+    let key = (inode.id(), offset / PAGE_SIZE);
+    if let Some(frame) = PAGE_CACHE.exclusive_access().get(&key) {
+        return Some(frame.clone());
+    }
+    // 分配页框时可能需要丢弃页缓存中的页，先释放页缓存
+    let frame = Arc::new(frame_alloc()?);
+    inode.read_at(offset, frame.ppn.get_bytes_array());
+    PAGE_CACHE.exclusive_access().insert(key, frame.clone());
+    Some(frame)
+}
+
+// 丢弃 id 对应的文件中第 first 到第 last 页
+fn remove_pages(id: usize, first: usize, last: usize) {
+    let mut cache = PAGE_CACHE.exclusive_access();
+    let keys: Vec<(usize, usize)> = cache
+        .range((id, first)..=(id, last))
+        .map(|(key, _)| *key)
+        .collect();
+    for key in keys {
+        cache.remove(&key);
+    }
+}
+
+// 文件 inode 的 [offset, offset + len) 被修改之后丢弃其中的页，之后再映射时重新读取。
+// 已经映射了这些页的进程继续使用原来的内容。
+pub fn invalidate(inode: &Inode, offset: usize, len: usize) {
+    if len > 0 {
+        remove_pages(
+            inode.id(),
+            offset / PAGE_SIZE,
+            (offset + len - 1) / PAGE_SIZE,
+        );
+    }
+}
+
+// 丢弃整个文件的页，比如文件被截断时
+pub fn invalidate_all(inode: &Inode) {
+    remove_pages(inode.id(), 0, usize::MAX);
+}
+
+// 丢弃最多 count 个没有被任何进程映射的页，返回释放的页框数。
+// 页缓存正在被访问时（比如正在为它分配页框）直接返回 0。
+pub fn shrink(count: usize) -> usize {
+    let mut cache = match PAGE_CACHE.try_exclusive_access() {
+        Some(cache) => cache,
+        None => return 0,
+    };
+    let keys: Vec<(usize, usize)> = cache
+        .iter()
+        .filter(|(_, frame)| Arc::strong_count(frame) == 1)
+        .map(|(key, _)| *key)
+        .take(count)
+        .collect();
+    for key in keys.iter() {
+        cache.remove(key);
+    }
+    keys.len()
+}
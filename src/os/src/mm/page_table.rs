@@ -73,12 +73,13 @@ pub struct PageTable {
 }
 
 impl PageTable {
-    pub fn new() -> Self {
-        let frame = frame_alloc().unwrap();
-        PageTable {
+    // 没有空闲的页框时返回 None
+    pub fn new() -> Option<Self> {
+        let frame = frame_alloc()?;
+        Some(PageTable {
             root_ppn: frame.ppn,
             frames: vec![frame],
-        }
+        })
     }
 
     pub fn from_token(satp: usize) -> Self {
@@ -89,7 +90,8 @@ impl PageTable {
     }
 
     // 查找并创建页表项 (page table entry)
-    // 如果在创建途中发现二级/三级页表没有被创建，则会自动通过 frame allocator 创建。
+    // 如果在创建途中发现二级/三级页表没有被创建，则会自动通过 frame allocator 创建，
+    // 没有空闲的页框时返回 None。
     fn find_pte_create(&mut self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
         let idxs = vpn.indexes();
         let mut ppn = self.root_ppn;
@@ -102,7 +104,7 @@ impl PageTable {
                 break;
             }
             if !pte.is_valid() {
-                let frame = frame_alloc().unwrap();
+                let frame = frame_alloc()?;
                 *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
                 self.frames.push(frame);
             }
@@ -132,11 +134,16 @@ impl PageTable {
         result
     }
 
-    #[allow(unused)]
-    pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
-        let pte = self.find_pte_create(vpn).unwrap();
+    // 建立 vpn 到 ppn 的映射，没有空闲的页框用来创建页表时返回 false
+    #[must_use]
+    pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) -> bool {
+        let pte = match self.find_pte_create(vpn) {
+            Some(pte) => pte,
+            None => return false,
+        };
         assert!(!pte.is_valid(), "vpn {:?} is mapped before mapping", vpn);
         *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+        true
     }
 
     #[allow(unused)]
@@ -146,6 +153,27 @@ impl PageTable {
         *pte = PageTableEntry::empty();
     }
 
+    // 修改一个已经映射的 vpn 的权限，ppn 保持不变
+    pub fn set_flags(&mut self, vpn: VirtPageNum, flags: PTEFlags) {
+        let pte = self.find_pte(vpn).unwrap();
+        assert!(
+            pte.is_valid(),
+            "vpn {:?} is invalid before setting flags",
+            vpn
+        );
+        *pte = PageTableEntry::new(pte.ppn(), flags | PTEFlags::V);
+    }
+
+    // 返回 vpn 自上次检查以来是否被访问过（页表项的 A 位），并清除 A 位
+    pub fn test_and_clear_accessed(&mut self, vpn: VirtPageNum) -> bool {
+        let pte = self.find_pte(vpn).unwrap();
+        let accessed = pte.flags().contains(PTEFlags::A);
+        if accessed {
+            *pte = PageTableEntry::new(pte.ppn(), pte.flags() - PTEFlags::A);
+        }
+        accessed
+    }
+
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.find_pte(vpn).map(|pte| *pte)
     }
@@ -223,13 +251,55 @@ pub fn translated_ref_mut<T>(token: usize, ptr: *mut T) -> &'static mut T {
         .get_mut()
 }
 
-/// 翻译 *const T 类型（token 下虚拟地址）为的 &'static T 类型
-pub fn translated_ref<T>(token: usize, ptr: *const T) -> &'static T {
-    let page_table = PageTable::from_token(token);
-    page_table
-        .translate_va(VirtAddr::from(ptr as usize))
-        .unwrap()
-        .get_ref()
+/// 检查 token 地址空间中 [start, start + len) 是否全部映射为用户可访问，
+/// writable 为 true 时还要求可写。
+fn check_user_range(page_table: &PageTable, start: usize, len: usize, writable: bool) -> bool {
+    let mut vpn = VirtAddr::from(start).floor();
+    let end_vpn = VirtAddr::from(start + len).ceil();
+    while vpn < end_vpn {
+        match page_table.translate(vpn) {
+            Some(pte)
+                if pte.is_valid()
+                    && pte.flags().contains(PTEFlags::U)
+                    && (!writable || pte.writable()) => {}
+            _ => return false,
+        }
+        vpn.step();
+    }
+    true
+}
+
+/// 将 value 按字节拷贝到 token 地址空间的 ptr 处，可以跨越页边界，
+/// 目标地址没有映射为用户可写时返回 false。
+pub fn copy_to_user<T: Copy>(token: usize, ptr: *mut T, value: &T) -> bool {
+    let len = core::mem::size_of::<T>();
+    if !check_user_range(&PageTable::from_token(token), ptr as usize, len, true) {
+        return false;
+    }
+    let src = unsafe { core::slice::from_raw_parts(value as *const T as *const u8, len) };
+    let mut start = 0;
+    for buf in translated_byte_buffer(token, ptr as *const u8, len) {
+        buf.copy_from_slice(&src[start..start + buf.len()]);
+        start += buf.len();
+    }
+    true
+}
+
+/// 从 token 地址空间的 ptr 处按字节读取一个 T，可以跨越页边界，
+/// 源地址没有映射为用户可访问时返回 None。
+pub fn copy_from_user<T: Copy>(token: usize, ptr: *const T) -> Option<T> {
+    let len = core::mem::size_of::<T>();
+    if !check_user_range(&PageTable::from_token(token), ptr as usize, len, false) {
+        return None;
+    }
+    let mut value = core::mem::MaybeUninit::<T>::uninit();
+    let dst = unsafe { core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, len) };
+    let mut start = 0;
+    for buf in translated_byte_buffer(token, ptr as *const u8, len) {
+        dst[start..start + buf.len()].copy_from_slice(buf);
+        start += buf.len();
+    }
+    Some(unsafe { value.assume_init() })
 }
 
 pub struct UserBuffer {
@@ -0,0 +1,104 @@
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use lazy_static::*;
+
+use crate::{
+    config::PAGE_SIZE,
+    errno::{EEXIST, EINVAL, ENOENT, ENOMEM},
+    sync::UPSafeCell,
+};
+
+use super::{
+    address::PhysPageNum,
+    frame_allocator::{frame_alloc, frame_stats, FrameTracker},
+};
+
+// 共享内存段，页框在创建时一次性分配。
+// 共享内存表和每个映射了它的逻辑段各持有一个引用，段被删除并且所有的映射
+// 都被解除之后页框才会被释放，所以它可以比创建它的进程存在得更久。
+pub struct SharedMemory {
+    key: usize,
+    frames: Vec<FrameTracker>,
+}
+
+impl SharedMemory {
+    // 共享内存段的大小（字节）
+    pub fn size(&self) -> usize {
+        self.frames.len() * PAGE_SIZE
+    }
+
+    // 共享内存段中第 page 页的页框
+    pub fn ppn(&self, page: usize) -> PhysPageNum {
+        self.frames[page].ppn
+    }
+}
+
+// 系统中所有没有被删除的共享内存段，以 id 为索引
+struct ShmTable {
+    segments: BTreeMap<usize, Arc<SharedMemory>>,
+    next_id: usize,
+}
+
+lazy_static! {
+    static ref SHM_TABLE: UPSafeCell<ShmTable> = unsafe {
+        UPSafeCell::new(ShmTable {
+            segments: BTreeMap::new(),
+            next_id: 1,
+        })
+    };
+}
+
+// 与 Linux 的 IPC_PRIVATE 一致，总是创建一个新的共享内存段
+pub const SHM_KEY_PRIVATE: usize = 0;
+
+// 返回 key 对应的共享内存段的 id。段不存在并且 create 为 true 时创建一个
+// 大小为 size 字节（按页向上取整）的段，页框被初始化为 0。
+// 错误时返回 -errno：段不存在 (ENOENT)，exclusive 时段已经存在 (EEXIST)，
+// size 不合法、超过已有段的大小或者超过物理内存的总页框数 (EINVAL)，
+// 没有足够的页框 (ENOMEM)。
+pub fn shm_get(key: usize, size: usize, create: bool, exclusive: bool) -> Result<usize, isize> {
+    let mut table = SHM_TABLE.exclusive_access();
+    if key != SHM_KEY_PRIVATE {
+        if let Some((id, shm)) = table.segments.iter().find(|(_, shm)| shm.key == key) {
+            if create && exclusive {
+                return Err(-EEXIST);
+            }
+            if size > shm.size() {
+                return Err(-EINVAL);
+            }
+            return Ok(*id);
+        }
+        if !create {
+            return Err(-ENOENT);
+        }
+    }
+    // 页框总数相当于 Linux 的 SHMMAX，更大的段无论如何都分配不出来
+    let pages = match size.checked_add(PAGE_SIZE - 1) {
+        Some(end) if size != 0 && end / PAGE_SIZE <= frame_stats().total => end / PAGE_SIZE,
+        _ => return Err(-EINVAL),
+    };
+    // 分配页框时可能需要换出用户页，先释放共享内存表
+    drop(table);
+    let frames = match (0..pages)
+        .map(|_| frame_alloc())
+        .collect::<Option<Vec<_>>>()
+    {
+        Some(frames) => frames,
+        None => return Err(-ENOMEM),
+    };
+    let mut table = SHM_TABLE.exclusive_access();
+    let id = table.next_id;
+    table.next_id += 1;
+    table
+        .segments
+        .insert(id, Arc::new(SharedMemory { key, frames }));
+    Ok(id)
+}
+
+pub fn shm_find(id: usize) -> Option<Arc<SharedMemory>> {
+    SHM_TABLE.exclusive_access().segments.get(&id).cloned()
+}
+
+// 删除共享内存段，已经映射了它的进程仍然可以继续使用，id 不存在时返回 false
+pub fn shm_remove(id: usize) -> bool {
+    SHM_TABLE.exclusive_access().segments.remove(&id).is_some()
+}
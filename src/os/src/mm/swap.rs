@@ -0,0 +1,98 @@
+use alloc::vec::Vec;
+use easy_fs::BLOCK_SIZE;
+use lazy_static::*;
+
+use crate::{
+    config::{PAGE_SIZE, SWAP_BLOCKS, SWAP_START_BLOCK},
+    drivers::block::BLOCK_DEVICE,
+    sync::UPSafeCell,
+};
+
+use super::address::PhysPageNum;
+
+// 每个 slot 保存一个被换出的页
+const BLOCKS_PER_SLOT: usize = PAGE_SIZE / BLOCK_SIZE;
+
+// SwapArea 管理块设备上的交换区，交换区被划分为若干个 slot。
+pub struct SwapArea {
+    used: Vec<bool>,
+    // 下一次从这里开始寻找空闲的 slot
+    next: usize,
+}
+
+impl SwapArea {
+    fn new() -> Self {
+        Self {
+            used: vec![false; SWAP_BLOCKS / BLOCKS_PER_SLOT],
+            next: 0,
+        }
+    }
+
+    fn alloc(&mut self) -> Option<usize> {
+        let count = self.used.len();
+        let slot = (0..count)
+            .map(|i| (self.next + i) % count)
+            .find(|slot| !self.used[*slot])?;
+        self.used[slot] = true;
+        self.next = (slot + 1) % count;
+        Some(slot)
+    }
+
+    fn dealloc(&mut self, slot: usize) {
+        assert!(
+            self.used[slot],
+            "swap slot {} has not been allocated!",
+            slot
+        );
+        self.used[slot] = false;
+    }
+}
+
+lazy_static! {
+    static ref SWAP_AREA: UPSafeCell<SwapArea> = unsafe { UPSafeCell::new(SwapArea::new()) };
+}
+
+fn slot_block(slot: usize, i: usize) -> usize {
+    SWAP_START_BLOCK + slot * BLOCKS_PER_SLOT + i
+}
+
+// 把页框 ppn 的内容写入一个新的 slot，返回 slot 的编号，交换区已满时返回 None
+pub fn swap_out(ppn: PhysPageNum) -> Option<usize> {
+    let slot = SWAP_AREA.exclusive_access().alloc()?;
+    let bytes = ppn.get_bytes_array();
+    for i in 0..BLOCKS_PER_SLOT {
+        BLOCK_DEVICE.write_block(
+            slot_block(slot, i),
+            &bytes[i * BLOCK_SIZE..(i + 1) * BLOCK_SIZE],
+        );
+    }
+    Some(slot)
+}
+
+// 把 slot 的内容读入页框 ppn 并释放 slot
+pub fn swap_in(slot: usize, ppn: PhysPageNum) {
+    let bytes = ppn.get_bytes_array();
+    for i in 0..BLOCKS_PER_SLOT {
+        BLOCK_DEVICE.read_block(
+            slot_block(slot, i),
+            &mut bytes[i * BLOCK_SIZE..(i + 1) * BLOCK_SIZE],
+        );
+    }
+    swap_free(slot);
+}
+
+// 复制 slot 的内容到一个新的 slot 中，fork 出的子进程需要一份独立的拷贝。
+// 交换区已满时返回 None。
+pub fn swap_dup(slot: usize) -> Option<usize> {
+    let new_slot = SWAP_AREA.exclusive_access().alloc()?;
+    let mut buf = [0u8; BLOCK_SIZE];
+    for i in 0..BLOCKS_PER_SLOT {
+        BLOCK_DEVICE.read_block(slot_block(slot, i), &mut buf);
+        BLOCK_DEVICE.write_block(slot_block(new_slot, i), &buf);
+    }
+    Some(new_slot)
+}
+
+pub fn swap_free(slot: usize) {
+    SWAP_AREA.exclusive_access().dealloc(slot);
+}
@@ -0,0 +1,34 @@
+use lazy_static::*;
+
+use crate::{sync::UPSafeCell, timer};
+
+// 内核随机数发生器，使用 xorshift64* 算法。它只用于地址空间布局随机化这类不需要
+// 密码学强度的场合。每次取随机数时都混入当前的时间，结果随各种事件发生的时机而变化。
+lazy_static! {
+    static ref RNG_STATE: UPSafeCell<u64> = unsafe { UPSafeCell::new(0x9e37_79b9_7f4a_7c15) };
+}
+
+// 用启动时的时间作为种子
+pub fn init() {
+    *RNG_STATE.exclusive_access() ^= timer::get_time() as u64;
+    random();
+}
+
+pub fn random() -> u64 {
+    let mut state = RNG_STATE.exclusive_access();
+    let mut x = *state ^ (timer::get_time() as u64).rotate_left(32);
+    // xorshift 的状态不能为 0
+    if x == 0 {
+        x = 0x9e37_79b9_7f4a_7c15;
+    }
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    *state = x;
+    x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+}
+
+// 返回 [0, bound) 中的随机数
+pub fn random_below(bound: usize) -> usize {
+    (random() % bound as u64) as usize
+}
@@ -1,7 +1,16 @@
 use crate::{
+    config::RESERVED_FRAMES,
+    errno::ENOMEM,
     fs::{inode::OpenFlags, open_file, pipe},
-    mm::page_table::{translated_byte_buffer, translated_ref_mut, translated_str, UserBuffer},
-    task::processor::{current_task, current_user_token},
+    mm::{
+        frame_allocator,
+        memory_set::{MapPermission, PageFaultError},
+        page_table::{translated_byte_buffer, translated_str, UserBuffer},
+    },
+    task::{
+        self,
+        processor::{current_task, current_user_token},
+    },
 };
 
 /// write buf of length `len` to a file with `fd`
@@ -16,11 +25,8 @@ pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
         if !file.writable() {
             return -1;
         }
-        return file.write(UserBuffer::new(translated_byte_buffer(
-            current_user_token(),
-            buf,
-            len,
-        ))) as isize;
+        // 缓冲区可能位于还没有加载的页中
+        return with_user_buffer(buf, len, MapPermission::R, |buf| file.write(buf));
     }
     -1
 }
@@ -43,14 +49,51 @@ pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
     if !file.readable() {
         return -1;
     }
-    file.read(UserBuffer::new(translated_byte_buffer(
-        current_user_token(),
-        buf,
-        len,
-    ))) as isize
+    with_user_buffer(buf, len, MapPermission::W, |buf| file.read(buf))
+}
+
+// 内核直接访问用户缓冲区 [buf, buf + len) 所在的页框：先处理其中的缺页，并在 f 执行
+// 期间（比如阻塞的 read）禁止换出当前进程的页。缓冲区不合法时返回 -1，
+// 没有足够的页框时返回 -ENOMEM。
+fn with_user_buffer(
+    buf: *const u8,
+    len: usize,
+    access: MapPermission,
+    f: impl FnOnce(UserBuffer) -> isize,
+) -> isize {
+    let task = current_task().unwrap();
+    let task_inner = task.inner_exclusive_access();
+    let missing = match task_inner
+        .memory_set
+        .missing_pages(buf as usize, len, access)
+    {
+        Some(missing) => missing,
+        None => return -1,
+    };
+    drop(task_inner);
+    // 处理缺页时当前进程的页不能被换出，需要为还没有驻留的页提前准备好页框。
+    // 换出其他进程的页时需要访问它们的 inner，所以这里不能持有当前进程的 inner。
+    if !frame_allocator::frame_reserve(missing + RESERVED_FRAMES) {
+        return -ENOMEM;
+    }
+    let mut task_inner = task.inner_exclusive_access();
+    match task_inner.memory_set.populate(buf as usize, len, access) {
+        Ok(()) => {}
+        Err(PageFaultError::OutOfMemory) => return -ENOMEM,
+        Err(PageFaultError::Illegal) => return -1,
+    }
+    task_inner.memory_set.pin();
+    let token = task_inner.get_user_token();
+    drop(task_inner);
+    let ret = f(UserBuffer::new(translated_byte_buffer(token, buf, len)));
+    task.inner_exclusive_access().memory_set.unpin();
+    ret
 }
 
 pub fn sys_open(path: *const u8, flags: u32) -> isize {
+    if !task::current_populate_str(path as usize) {
+        return -1;
+    }
     let token = current_user_token();
     let name = translated_str(token, path);
     let file = open_file(name.as_str(), OpenFlags::from_bits(flags).unwrap());
@@ -82,7 +125,6 @@ pub fn sys_close(fd: usize) -> isize {
 pub fn sys_pipe(pipe: *mut usize) -> isize {
     let task = current_task().unwrap();
     let mut task_inner = task.inner_exclusive_access();
-    let token = current_user_token();
     // 创建 pipes 并保存到进程的 fd_table 中
     let (read_p, write_p) = pipe::make_pipe();
     let read_fd = task_inner.alloc_fd();
@@ -90,8 +132,14 @@ pub fn sys_pipe(pipe: *mut usize) -> isize {
     let write_fd = task_inner.alloc_fd();
     task_inner.fd_table[write_fd] = Some(write_p);
     // 将 read_fd 和 write_fd 传递给用户
-    *translated_ref_mut(token, pipe) = read_fd;
-    *translated_ref_mut(token, unsafe { pipe.add(1) }) = write_fd;
+    if !task_inner
+        .memory_set
+        .copy_to_user(pipe as *mut [usize; 2], &[read_fd, write_fd])
+    {
+        task_inner.fd_table[read_fd].take();
+        task_inner.fd_table[write_fd].take();
+        return -1;
+    }
     0
 }
 
@@ -109,3 +157,17 @@ pub fn sys_dup(fd: usize) -> isize {
     task_inner.fd_table[new_fd] = task_inner.fd_table[fd].clone();
     new_fd as isize
 }
+
+/// 对 fd 指向的设备执行控制命令，目前只有控制台支持
+pub fn sys_ioctl(fd: usize, cmd: usize, arg: usize) -> isize {
+    let task = current_task().unwrap();
+    let task_inner = task.inner_exclusive_access();
+    if fd >= task_inner.fd_table.len() {
+        return -1;
+    }
+    if let Some(file) = task_inner.fd_table[fd].clone() {
+        drop(task_inner);
+        return file.ioctl(cmd, arg);
+    }
+    -1
+}
@@ -0,0 +1,329 @@
+use crate::{
+    config::{MMAP_BASE, MMAP_END, PAGE_SIZE, USER_W_XOR_X},
+    errno::{EACCES, EBADF, EINVAL, ENOMEM},
+    mm::{
+        address::{VirtAddr, VirtPageNum},
+        memory_set::{MapPermission, MapType},
+        shm,
+    },
+    task::processor::current_task,
+};
+
+// mmap 的 prot，取值与 Linux 保持一致
+const PROT_READ: usize = 0x1;
+const PROT_WRITE: usize = 0x2;
+const PROT_EXEC: usize = 0x4;
+
+// mmap 的 flags，取值与 Linux 保持一致
+const MAP_SHARED: usize = 0x01;
+const MAP_PRIVATE: usize = 0x02;
+const MAP_FIXED: usize = 0x10;
+const MAP_ANONYMOUS: usize = 0x20;
+
+// msync 的 flags，取值与 Linux 保持一致。写回总是同步完成的。
+const MS_ASYNC: usize = 0x1;
+const MS_INVALIDATE: usize = 0x2;
+const MS_SYNC: usize = 0x4;
+
+// shmget、shmat 和 shmctl 的参数，取值与 Linux 保持一致
+const IPC_CREAT: usize = 0o1000;
+const IPC_EXCL: usize = 0o2000;
+const IPC_RMID: usize = 0;
+const SHM_RDONLY: usize = 0o10000;
+
+// 把 prot 转换为逻辑段的权限。RISC-V 的页表项不允许可写但不可读，
+// 并且 R/W/X 全为 0 的页表项表示指向下一级页表，所以不支持 PROT_NONE。
+fn prot_to_permission(prot: usize) -> Option<MapPermission> {
+    if prot & !(PROT_READ | PROT_WRITE | PROT_EXEC) != 0 || prot == 0 {
+        return None;
+    }
+    let mut permission = MapPermission::U;
+    if prot & (PROT_READ | PROT_WRITE) != 0 {
+        permission |= MapPermission::R;
+    }
+    if prot & PROT_WRITE != 0 {
+        permission |= MapPermission::W;
+    }
+    if prot & PROT_EXEC != 0 {
+        permission |= MapPermission::X;
+    }
+    Some(permission)
+}
+
+// 开启了 W^X 时不允许同时可写和可执行的权限
+fn violates_w_xor_x(permission: MapPermission) -> bool {
+    USER_W_XOR_X && permission.contains(MapPermission::W | MapPermission::X)
+}
+
+// 检查 [addr, addr + len) 是否位于 mmap 区域中，返回对应的虚拟页范围
+fn mmap_range(addr: usize, len: usize) -> Option<(VirtPageNum, VirtPageNum)> {
+    if addr % PAGE_SIZE != 0 || len == 0 {
+        return None;
+    }
+    let end = addr.checked_add(len)?;
+    if addr < MMAP_BASE || end > MMAP_END {
+        return None;
+    }
+    Some((VirtAddr::from(addr).floor(), VirtAddr::from(end).ceil()))
+}
+
+/// 映射一段内存，返回映射的起始地址。addr 只是一个提示，设置了
+/// MAP_FIXED 时必须映射到 addr，原来与之重叠的映射会被解除。
+/// 设置了 MAP_ANONYMOUS 时映射一段初始化为 0 的匿名内存，目前只支持
+/// MAP_PRIVATE；否则映射文件 fd 从 offset 开始的内容，offset 需要按页对齐。
+/// MAP_SHARED 的文件映射被修改后会写回文件，MAP_PRIVATE 的修改只对当前进程可见。
+/// 失败时返回 -errno：参数不合法 (EINVAL)，fd 不合法 (EBADF)，文件的打开方式不允许
+/// 这样的映射或者违反 W^X (EACCES)，mmap 区域中没有足够的空间 (ENOMEM)。
+pub fn sys_mmap(
+    addr: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    if flags & !(MAP_SHARED | MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED) != 0 {
+        return -EINVAL;
+    }
+    let shared = match flags & (MAP_SHARED | MAP_PRIVATE) {
+        MAP_SHARED => true,
+        MAP_PRIVATE => false,
+        _ => return -EINVAL,
+    };
+    let anonymous = flags & MAP_ANONYMOUS != 0;
+    if anonymous && shared {
+        return -EINVAL;
+    }
+    let permission = match prot_to_permission(prot) {
+        Some(permission) if violates_w_xor_x(permission) => return -EACCES,
+        Some(permission) => permission,
+        None => return -EINVAL,
+    };
+    let page_count = match len.checked_add(PAGE_SIZE - 1) {
+        Some(len) if len >= PAGE_SIZE => len / PAGE_SIZE,
+        _ => return -EINVAL,
+    };
+
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let mut max_permission = MapPermission::all();
+    let inode = if anonymous {
+        None
+    } else {
+        if offset % PAGE_SIZE != 0 {
+            return -EINVAL;
+        }
+        let file = match task_inner.fd_table.get(fd) {
+            Some(Some(file)) => file.clone(),
+            _ => return -EBADF,
+        };
+        // 文件映射总是需要读取文件，共享的可写映射还需要写回文件
+        if !file.readable() || (shared && prot & PROT_WRITE != 0 && !file.writable()) {
+            return -EACCES;
+        }
+        // 不能写回的共享映射之后也不能通过 mprotect 改为可写
+        if shared && !file.writable() {
+            max_permission.remove(MapPermission::W);
+        }
+        // 管道等没有 inode 的文件不能被映射
+        match file.inode() {
+            Some(inode) => Some(inode),
+            None => return -EACCES,
+        }
+    };
+    let memory_set = &mut task_inner.memory_set;
+    let start = if flags & MAP_FIXED != 0 {
+        let (start, end) = match mmap_range(addr, page_count * PAGE_SIZE) {
+            Some(range) => range,
+            None => return -EINVAL,
+        };
+        memory_set.remove_range(start, end);
+        start
+    } else {
+        // 提示的地址可用时使用它，否则在 mmap 区域中寻找
+        match mmap_range(addr, page_count * PAGE_SIZE) {
+            Some((start, end)) if memory_set.is_free(start, end) => start,
+            _ => match memory_set.find_free_range(
+                VirtAddr::from(memory_set.mmap_base()).floor(),
+                VirtAddr::from(MMAP_END).floor(),
+                page_count,
+            ) {
+                Some(start) => start,
+                None => return -ENOMEM,
+            },
+        }
+    };
+    let start_va = VirtAddr::from(start);
+    let end_va = VirtAddr::from(VirtPageNum(start.0 + page_count));
+    match inode {
+        Some(inode) => {
+            let map_type = MapType::File {
+                inode,
+                offset,
+                shared,
+            };
+            memory_set.insert_file_area(start_va, end_va, permission, max_permission, map_type)
+        }
+        None => memory_set.insert_lazy_area(start_va, end_va, permission),
+    }
+    usize::from(start_va) as isize
+}
+
+/// 把进程的 program break 设置为 addr，返回新的 program break。
+/// addr 为 0 或者无法调整到 addr 时保持不变，返回当前的 program break，
+/// 与 Linux 的 brk 系统调用一致。
+pub fn sys_brk(addr: usize) -> isize {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    if addr != 0 {
+        task_inner.memory_set.set_brk(addr);
+    }
+    task_inner.memory_set.brk() as isize
+}
+
+/// 解除 [addr, addr + len) 的映射，只能作用于 mmap 区域。部分重叠的映射
+/// 会被拆分，没有被映射的部分直接忽略。
+pub fn sys_munmap(addr: usize, len: usize) -> isize {
+    let (start, end) = match mmap_range(addr, len) {
+        Some(range) => range,
+        None => return -1,
+    };
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.memory_set.remove_range(start, end);
+    0
+}
+
+/// 把 [addr, addr + len) 的权限修改为 prot，可以作用于用户地址空间中的任何映射，
+/// 比如 ELF 的段、堆和栈。addr 需要按页对齐，len 按页向上取整。
+/// 范围中有没有被映射的页时返回 -ENOMEM，违反 W^X 或者超出了映射建立时允许的
+/// 最大权限（比如只读打开的文件的共享映射、SHM_RDONLY 的共享内存）时返回 -EACCES。
+pub fn sys_mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    if addr % PAGE_SIZE != 0 {
+        return -EINVAL;
+    }
+    let permission = match prot_to_permission(prot) {
+        Some(permission) => permission,
+        None => return -EINVAL,
+    };
+    if violates_w_xor_x(permission) {
+        return -EACCES;
+    }
+    let end = match addr.checked_add(len) {
+        Some(end) => end,
+        None => return -ENOMEM,
+    };
+    if len == 0 {
+        return 0;
+    }
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    match task_inner.memory_set.protect(
+        VirtAddr::from(addr).floor(),
+        VirtAddr::from(end).ceil(),
+        permission,
+    ) {
+        Ok(()) => 0,
+        Err(errno) => errno,
+    }
+}
+
+/// 把 [addr, addr + len) 中 MAP_SHARED 文件映射被修改过的页写回文件。
+/// MS_ASYNC 和 MS_SYNC 不能同时设置，两者都会在返回前完成写回。
+pub fn sys_msync(addr: usize, len: usize, flags: usize) -> isize {
+    if flags & !(MS_ASYNC | MS_INVALIDATE | MS_SYNC) != 0
+        || flags & (MS_ASYNC | MS_SYNC) == MS_ASYNC | MS_SYNC
+    {
+        return -1;
+    }
+    let (start, end) = match mmap_range(addr, len) {
+        Some(range) => range,
+        None => return -1,
+    };
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.memory_set.sync_range(start, end);
+    0
+}
+
+/// 返回 key 对应的共享内存段的 id，key 为 IPC_PRIVATE 时总是创建新的段。
+/// 段不存在并且设置了 IPC_CREAT 时创建一个 size 字节的段，同时设置了 IPC_EXCL
+/// 时要求段不存在。flags 中的访问权限位被忽略。
+pub fn sys_shmget(key: usize, size: usize, flags: usize) -> isize {
+    match shm::shm_get(key, size, flags & IPC_CREAT != 0, flags & IPC_EXCL != 0) {
+        Ok(id) => id as isize,
+        Err(errno) => errno,
+    }
+}
+
+/// 把共享内存段 id 映射到当前进程的 mmap 区域，返回映射的起始地址。
+/// addr 不为 0 时必须按页对齐并且没有被占用；设置了 SHM_RDONLY 时只读。
+pub fn sys_shmat(id: usize, addr: usize, flags: usize) -> isize {
+    if flags & !SHM_RDONLY != 0 {
+        return -EINVAL;
+    }
+    let segment = match shm::shm_find(id) {
+        Some(segment) => segment,
+        None => return -EINVAL,
+    };
+    let mut permission = MapPermission::U | MapPermission::R;
+    let mut max_permission = MapPermission::all();
+    if flags & SHM_RDONLY == 0 {
+        permission |= MapPermission::W;
+    } else {
+        // 只读的映射之后也不能通过 mprotect 改为可写
+        max_permission.remove(MapPermission::W);
+    }
+    let page_count = segment.size() / PAGE_SIZE;
+
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let memory_set = &mut task_inner.memory_set;
+    let start = if addr != 0 {
+        match mmap_range(addr, segment.size()) {
+            Some((start, end)) if memory_set.is_free(start, end) => start,
+            _ => return -EINVAL,
+        }
+    } else {
+        match memory_set.find_free_range(
+            VirtAddr::from(memory_set.mmap_base()).floor(),
+            VirtAddr::from(MMAP_END).floor(),
+            page_count,
+        ) {
+            Some(start) => start,
+            None => return -EINVAL,
+        }
+    };
+    let start_va = VirtAddr::from(start);
+    let end_va = VirtAddr::from(VirtPageNum(start.0 + page_count));
+    if !memory_set.insert_shared_area(start_va, end_va, permission, max_permission, segment) {
+        return -ENOMEM;
+    }
+    usize::from(start_va) as isize
+}
+
+/// 解除 shmat 在 addr 处建立的共享内存映射
+pub fn sys_shmdt(addr: usize) -> isize {
+    if addr % PAGE_SIZE != 0 {
+        return -EINVAL;
+    }
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    if task_inner
+        .memory_set
+        .remove_shared_area(VirtAddr::from(addr).floor())
+    {
+        0
+    } else {
+        -EINVAL
+    }
+}
+
+/// 目前只支持 IPC_RMID：删除共享内存段，之后 shmget 无法再找到它，
+/// 已经映射了它的进程可以继续使用，最后一个映射被解除时释放页框。
+pub fn sys_shmctl(id: usize, cmd: usize, _buf: usize) -> isize {
+    if cmd != IPC_RMID || !shm::shm_remove(id) {
+        return -EINVAL;
+    }
+    0
+}
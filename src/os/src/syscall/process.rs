@@ -1,19 +1,29 @@
 use alloc::{string::String, sync::Arc, vec::Vec};
 
 use crate::{
-    fs::{inode::OpenFlags, open_file},
-    mm::page_table::{self, translated_ref, translated_ref_mut},
+    errno::{EAGAIN, EINTR, EINVAL, ENOMEM},
+    fs::{inode::OpenFlags, open_file, File},
+    mm::{memory_set::LoadError, page_table},
     task::{
         self,
         manager::{self, get_task_by_pid},
-        processor::{self, current_task, current_user_token},
-        SignalAction, SignalFlags, MAX_SIG,
+        processor::{self, current_task},
+        ITimerVal, SignalAction, SignalActionFlags, SignalFlags, SignalFrame, SignalInfo,
+        SignalStack, CONTINUED_STATUS, ITIMER_PROF, MINSIGSTKSZ, SS_DISABLE, SS_ONSTACK,
     },
     timer,
 };
 
+const SIG_BLOCK: usize = 0;
+const SIG_UNBLOCK: usize = 1;
+const SIG_SETMASK: usize = 2;
+
 const ANY_PROCESS: isize = -1;
 
+// waitpid 的 options，取值与 Linux 保持一致
+const WUNTRACED: usize = 2;
+const WCONTINUED: usize = 8;
+
 const NO_CHILDREN_RUNNING: isize = -1;
 const CHILDREN_RUNNING: isize = -2;
 
@@ -38,7 +48,10 @@ pub fn sys_getpid() -> isize {
 
 pub fn sys_fork() -> isize {
     let parent_tcb = processor::current_task().unwrap();
-    let child_tcb = parent_tcb.fork();
+    let child_tcb = match parent_tcb.fork() {
+        Some(child_tcb) => child_tcb,
+        None => return -ENOMEM,
+    };
     let child_pid = child_tcb.getpid();
     let mut child_trap_cx = child_tcb.inner_exclusive_access().get_trap_cx();
     // child process's return value is 0
@@ -53,24 +66,41 @@ pub fn sys_fork() -> isize {
 /// args 表示用户程序的参数，类型是 [&str]，数据为 0 表明没有更多的参数了
 pub fn sys_exec(path: *const u8, mut args: *const usize) -> isize {
     let token = processor::current_user_token();
+    if !task::current_populate_str(path as usize) {
+        return -1;
+    }
     let app_name = page_table::translated_str(token, path);
     // args
     let mut args_vec: Vec<String> = Vec::new();
     loop {
-        let arg_str_ptr = *page_table::translated_ref(token, args);
+        let arg_str_ptr = match current_task()
+            .unwrap()
+            .inner_exclusive_access()
+            .memory_set
+            .copy_from_user(args)
+        {
+            Some(ptr) => ptr,
+            None => return -1,
+        };
         if arg_str_ptr == 0 {
             break;
         }
+        if !task::current_populate_str(arg_str_ptr) {
+            return -1;
+        }
         args_vec.push(page_table::translated_str(token, arg_str_ptr as *const u8));
         unsafe { args = args.add(1) }
     }
     if let Some(inode) = open_file(app_name.as_str(), OpenFlags::READ_ONLY) {
-        let data = inode.read_all();
         let argc = args_vec.len();
-        processor::current_task()
+        return match processor::current_task()
             .unwrap()
-            .exec(data.as_slice(), args_vec);
-        return argc as isize;
+            .exec(inode.inode().unwrap(), args_vec)
+        {
+            Ok(()) => argc as isize,
+            Err(LoadError::InvalidElf) => -1,
+            Err(LoadError::OutOfMemory) => -ENOMEM,
+        };
     } else {
         println!(
             "[kernel] Syscall exec error due to opening \"{}\"",
@@ -81,10 +111,12 @@ pub fn sys_exec(path: *const u8, mut args: *const usize) -> isize {
 }
 
 // 返回数据有三种类型：
-// 1. 当关心的子进程处于 Zombie 状态时，返回该进程的 pid (pid >= 0)；
+// 1. 当关心的子进程处于 Zombie 状态时，返回该进程的 pid (pid >= 0)，exit_code 为退出码；
+//    options 包含 WUNTRACED/WCONTINUED 时，子进程被暂停/恢复也会返回它的 pid，
+//    exit_code 为 stopped_status/CONTINUED_STATUS；
 // 2. 当关心的子进程都已经退出时，返回 NO_CHILDREN_RUNNING；
 // 3. 当关心的子进程还没有退出时，返回 CHILDREN_RUNNING。
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32, options: usize) -> isize {
     let current_task = processor::current_task().unwrap();
     let mut current_task_inner = current_task.inner_exclusive_access();
 
@@ -111,8 +143,33 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
         assert_eq!(Arc::strong_count(&child), 1);
         let child_pid = child.getpid();
         let exit_code = child.inner_exclusive_access().exit_code;
-        *(page_table::translated_ref_mut(current_task_inner.get_user_token(), exit_code_ptr)) =
-            exit_code;
+        current_task_inner
+            .memory_set
+            .copy_to_user(exit_code_ptr, &exit_code);
+        return child_pid as isize;
+    }
+
+    // 每次暂停或恢复只会被报告一次
+    let report = current_task_inner.children.iter().find_map(|child| {
+        if pid != ANY_PROCESS && (pid as usize) != child.getpid() {
+            return None;
+        }
+        let mut child_inner = child.inner_exclusive_access();
+        let wanted = match child_inner.wait_report {
+            Some(CONTINUED_STATUS) => options & WCONTINUED != 0,
+            Some(_) => options & WUNTRACED != 0,
+            None => false,
+        };
+        if wanted {
+            Some((child.getpid(), child_inner.wait_report.take().unwrap()))
+        } else {
+            None
+        }
+    });
+    if let Some((child_pid, status)) = report {
+        current_task_inner
+            .memory_set
+            .copy_to_user(exit_code_ptr, &status);
         return child_pid as isize;
     }
 
@@ -120,80 +177,315 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
 }
 
 // 注册一个新的 signal action，返回原有的 signal action。
+// action 为空时只查询，old_action 为空时不返回原有的 signal action。
 pub fn sys_sigaction(
     signum: i32,
     action: *const SignalAction,
     old_action: *mut SignalAction,
 ) -> isize {
-    let token = current_user_token();
     if current_task().is_none() {
         return -1;
     }
     let task = current_task().unwrap();
     let mut task_inner = task.inner_exclusive_access();
-    if signum as usize > MAX_SIG {
-        return -1;
-    }
-    let flag = SignalFlags::from_bits(1 << signum);
+    let flag = SignalFlags::from_signum(signum as usize);
     if flag.is_none() {
         return -1;
     }
     let flag = flag.unwrap();
-    if action as usize == 0
-        || old_action as usize == 0
+    // SIGKILL 和 SIGSTOP 不能被捕获或忽略
+    if (action as usize == 0 && old_action as usize == 0)
         || flag == SignalFlags::SIGKILL
         || flag == SignalFlags::SIGSTOP
     {
         return -1;
     }
-    let old_action_from_kernel = task_inner.signal_actions.table[signum as usize];
-    *translated_ref_mut(token, old_action) = old_action_from_kernel;
-    task_inner.signal_actions.table[signum as usize] = *translated_ref(token, action);
+    if old_action as usize != 0 {
+        let old_action_from_kernel = task_inner.signal_actions.table[signum as usize];
+        if !task_inner
+            .memory_set
+            .copy_to_user(old_action, &old_action_from_kernel)
+        {
+            return -1;
+        }
+    }
+    if action as usize != 0 {
+        let mut action = match task_inner.memory_set.copy_from_user(action) {
+            Some(action) => action,
+            None => return -1,
+        };
+        // 过滤用户传入的非法位，SIGKILL 和 SIGSTOP 也不能被屏蔽
+        action.mask = SignalFlags::from_mask_bits(action.mask.bits());
+        action.flags = SignalActionFlags::from_bits_truncate(action.flags.bits());
+        task_inner.signal_actions.table[signum as usize] = action;
+    }
+    0
+}
+
+/// 把进程 pid 加入进程组 pgid，pid 只能是当前进程或者它的子进程。
+/// pid 为 0 表示当前进程，pgid 为 0 表示以 pid 为编号创建新的进程组。
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    let current = current_task().unwrap();
+    let target = if pid == 0 || pid == current.getpid() {
+        current.clone()
+    } else {
+        let current_inner = current.inner_exclusive_access();
+        match current_inner
+            .children
+            .iter()
+            .find(|child| child.getpid() == pid)
+        {
+            Some(child) => child.clone(),
+            None => return -1,
+        }
+    };
+    let pgid = if pgid == 0 { target.getpid() } else { pgid };
+    // 只能加入已经存在的进程组
+    if pgid != target.getpid()
+        && !manager::all_tasks()
+            .iter()
+            .any(|task| task.inner_exclusive_access().pgid == pgid)
+    {
+        return -1;
+    }
+    target.inner_exclusive_access().pgid = pgid;
     0
 }
 
-/// 发送信号
+/// 返回进程 pid 所在的进程组，pid 为 0 表示当前进程
+pub fn sys_getpgid(pid: usize) -> isize {
+    let task = if pid == 0 {
+        current_task()
+    } else {
+        get_task_by_pid(pid)
+    };
+    match task {
+        Some(task) => task.inner_exclusive_access().pgid as isize,
+        None => -1,
+    }
+}
+
+// 与 Linux 一致，参数为 0xffffffff 时只查询不修改
+const PERSONALITY_QUERY: usize = 0xffff_ffff;
+
+/// 设置当前进程的 personality，返回原来的值。ADDR_NO_RANDOMIZE 在下一次 exec 时生效。
+pub fn sys_personality(persona: usize) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let old = inner.personality;
+    if persona != PERSONALITY_QUERY {
+        inner.personality = persona;
+    }
+    old as isize
+}
+
+/// 发送信号，signum 为 0 时只检查进程是否存在
 // QUESTION(justxuewei): 为什么发送信号要叫 `sys_kill` 呢？
 pub fn sys_kill(pid: usize, signum: i32) -> isize {
+    let sender_pid = current_task().unwrap().getpid();
+    send_signal(
+        pid,
+        signum,
+        SignalInfo::from_user(signum as usize, sender_pid),
+    )
+}
+
+/// 发送一个附带数据的信号，实时信号会按照发送顺序排队递送
+pub fn sys_sigqueue(pid: usize, signum: i32, value: usize) -> isize {
+    let sender_pid = current_task().unwrap().getpid();
+    send_signal(
+        pid,
+        signum,
+        SignalInfo::from_queue(signum as usize, sender_pid, value),
+    )
+}
+
+fn send_signal(pid: usize, signum: i32, info: SignalInfo) -> isize {
     let task = get_task_by_pid(pid);
-    if task.is_none() {
+    if task.is_none() || signum < 0 {
         return -1;
     }
-    let flag = SignalFlags::from_bits(1 << signum);
+    let task = task.unwrap();
+    if signum == 0 {
+        return 0;
+    }
+    let flag = SignalFlags::from_signum(signum as usize);
     if flag.is_none() {
         return -1;
     }
-    let task = task.unwrap();
     let flag = flag.unwrap();
     let mut task_inner = task.inner_exclusive_access();
-    if task_inner.signals.contains(flag) {
-        return -1;
+    if !task_inner.add_signal(flag, info) {
+        return -EAGAIN;
+    }
+    drop(task_inner);
+    if flag == SignalFlags::SIGCONT || flag == SignalFlags::SIGKILL {
+        task::wake_stopped_task(task, flag);
     }
-    task_inner.signals.insert(flag);
     0
 }
 
-/// 信号处理结束，返回执行用户逻辑
+/// 信号处理结束，从最内层的信号帧中恢复被打断的用户上下文
 pub fn sys_sigreturn() -> isize {
     if let Some(task) = current_task() {
         let mut task_inner = task.inner_exclusive_access();
-        task_inner.handling_sig = -1;
-        let trap_ctx = task_inner.get_trap_cx();
-        *trap_ctx = task_inner.trap_ctx_backup.unwrap();
-        return 0;
+        if task_inner.signal_frame == 0 {
+            return -1;
+        }
+        let frame_ptr = task_inner.signal_frame as *const SignalFrame;
+        let frame = match task_inner.memory_set.copy_from_user(frame_ptr) {
+            Some(frame) => frame,
+            None => {
+                // 信号帧所在的用户栈已经失效，无法恢复上下文
+                task_inner.killed = Some(SignalFlags::SIGSEGV);
+                return -1;
+            }
+        };
+        task_inner.signal_frame = frame.prev;
+        task_inner.signal_mask = SignalFlags::from_mask_bits(frame.old_mask.bits());
+        // 信号帧中只有通用寄存器和 sepc，sstatus 和内核字段保持 trap context 中的值，
+        // 否则用户程序就可以借此进入内核态。
+        let trap_cx = task_inner.get_trap_cx();
+        trap_cx.x = frame.context.x;
+        trap_cx.sepc = frame.context.sepc;
+        // trap_handler 会把返回值写入 a0，所以这里返回被打断时的 a0
+        return trap_cx.x[10] as isize;
     }
     -1
 }
 
-/// 设置进程的信号掩码
-pub fn sys_procmask(mask: u32) -> isize {
-    if let Some(task) = current_task() {
-        let mut task_inner = task.inner_exclusive_access();
-        let old_mask = task_inner.signal_mask;
-        if let Some(flag) = SignalFlags::from_bits(mask) {
-            task_inner.signal_mask = flag;
-            return old_mask.bits() as isize;
+/// 设置或查询备用信号栈，ss 和 old_ss 都可以为空
+pub fn sys_sigaltstack(ss: *const SignalStack, old_ss: *mut SignalStack) -> isize {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let sp = task_inner.get_trap_cx().x[2];
+    let on_stack = task_inner.signal_stack.contains(sp);
+    if old_ss as usize != 0 {
+        let mut old = task_inner.signal_stack;
+        if on_stack {
+            old.flags |= SS_ONSTACK;
+        }
+        if !task_inner.memory_set.copy_to_user(old_ss, &old) {
+            return -1;
         }
     }
-    -1
+    if ss as usize != 0 {
+        let new = match task_inner.memory_set.copy_from_user(ss) {
+            Some(new) => new,
+            None => return -1,
+        };
+        // 正在备用信号栈上执行时不能修改它
+        if on_stack {
+            return -1;
+        }
+        if new.flags & SS_DISABLE != 0 {
+            task_inner.signal_stack = SignalStack::default();
+        } else if new.flags != 0 && new.flags != SS_ONSTACK {
+            return -1;
+        } else if new.size < MINSIGSTKSZ {
+            return -1;
+        } else {
+            task_inner.signal_stack = SignalStack {
+                sp: new.sp,
+                flags: 0,
+                size: new.size,
+            };
+        }
+    }
+    0
+}
+
+/// 修改进程的信号掩码，how 决定 set 如何作用于当前掩码。
+/// set 为空时只查询，oldset 不为空时返回原来的掩码。
+pub fn sys_sigprocmask(how: usize, set: *const u64, oldset: *mut u64) -> isize {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let old_mask = task_inner.signal_mask;
+    if set as usize != 0 {
+        let set = match task_inner.memory_set.copy_from_user(set) {
+            Some(bits) => SignalFlags::from_mask_bits(bits),
+            None => return -1,
+        };
+        task_inner.signal_mask = match how {
+            SIG_BLOCK => old_mask | set,
+            SIG_UNBLOCK => old_mask - set,
+            SIG_SETMASK => set,
+            _ => return -1,
+        };
+    }
+    if oldset as usize != 0 && !task_inner.memory_set.copy_to_user(oldset, &old_mask.bits()) {
+        return -1;
+    }
+    0
+}
+
+/// 返回因为被屏蔽而处于未决状态的信号
+pub fn sys_sigpending(set: *mut u64) -> isize {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let pending = task_inner.signals & task_inner.signal_mask;
+    if !task_inner.memory_set.copy_to_user(set, &pending.bits()) {
+        return -1;
+    }
+    0
+}
+
+/// 临时把信号掩码替换为 mask 并等待信号，替换掩码和开始等待之间不会错过信号。
+/// 被忽略的信号不会使它返回，信号处理函数执行后恢复原来的掩码并返回 EINTR。
+pub fn sys_sigsuspend(mask: *const u64) -> isize {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let mask = match task_inner.memory_set.copy_from_user(mask) {
+        Some(bits) => SignalFlags::from_mask_bits(bits),
+        None => return -1,
+    };
+    task_inner.saved_mask = Some(task_inner.signal_mask);
+    task_inner.signal_mask = mask;
+    drop(task_inner);
+    drop(task);
+    task::wait_for_signal();
+    -EINTR
+}
+
+/// 查询间隔定时器 which 的状态
+pub fn sys_getitimer(which: usize, curr_value: *mut ITimerVal) -> isize {
+    if which > ITIMER_PROF {
+        return -1;
+    }
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let curr = task_inner.itimers.get(which, timer::get_time_us());
+    if !task_inner.memory_set.copy_to_user(curr_value, &curr) {
+        return -1;
+    }
+    0
+}
+
+/// 设置间隔定时器 which，old_value 不为空时返回原来的状态
+pub fn sys_setitimer(
+    which: usize,
+    new_value: *const ITimerVal,
+    old_value: *mut ITimerVal,
+) -> isize {
+    if which > ITIMER_PROF {
+        return -1;
+    }
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let new = match task_inner.memory_set.copy_from_user(new_value) {
+        Some(new) => new,
+        None => return -1,
+    };
+    // usec 超出范围或者换算成微秒后溢出
+    let old = match task_inner.itimers.set(which, &new, timer::get_time_us()) {
+        Some(old) => old,
+        None => return -EINVAL,
+    };
+    if let Some(deadline) = task_inner.itimers.real_deadline() {
+        task::arm_real_timer(deadline);
+    }
+    if old_value as usize != 0 && !task_inner.memory_set.copy_to_user(old_value, &old) {
+        return -1;
+    }
+    0
 }
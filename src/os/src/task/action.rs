@@ -1,27 +1,45 @@
-use super::{SignalFlags, MAX_SIG};
+use bitflags::*;
+
+use super::{SignalFlags, MAX_SIG, SIG_DFL, SIG_IGN};
+
+// 取值与 Linux 保持一致
+bitflags! {
+    pub struct SignalActionFlags: u32 {
+        // 信号处理函数额外接收 SignalInfo 和信号帧的地址
+        const SA_SIGINFO = 0x4;
+        // 在 sigaltstack 设置的备用信号栈上执行信号处理函数
+        const SA_ONSTACK = 0x0800_0000;
+        // 被信号打断的阻塞系统调用在信号处理函数返回后自动重新执行
+        const SA_RESTART = 0x1000_0000;
+        // 执行信号处理函数时不屏蔽当前信号
+        const SA_NODEFER = 0x4000_0000;
+        // 信号递送后恢复为 SIG_DFL
+        const SA_RESETHAND = 0x8000_0000;
+    }
+}
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct SignalAction {
-    // 信号处理函数
+    // 信号处理函数，也可以是 SIG_DFL 或者 SIG_IGN
     pub handler: usize,
-    // 信号掩码
+    // 执行信号处理函数期间额外屏蔽的信号
     pub mask: SignalFlags,
+    pub flags: SignalActionFlags,
 }
 
 impl Default for SignalAction {
     fn default() -> Self {
         Self {
-            handler: 0,
-            // 40 -> 0b00101000: 屏蔽 SIGILL 和 SIGABRT
-            // TODO(justxuewei): 为啥要默认屏蔽这两个信号？
-            mask: SignalFlags::from_bits(40).unwrap(),
+            handler: SIG_DFL,
+            mask: SignalFlags::empty(),
+            flags: SignalActionFlags::empty(),
         }
     }
 }
 
 // 如果进程想要自定义信号的处理，需要在 SignalActions 中注册，信号和信号处理函数
-// 是一一对应的。
+// 是一一对应的。table 以信号编号为下标，table[0] 不使用。
 #[derive(Clone)]
 pub struct SignalActions {
     pub table: [SignalAction; MAX_SIG + 1],
@@ -34,3 +52,15 @@ impl Default for SignalActions {
         }
     }
 }
+
+impl SignalActions {
+    /// exec 之后原有的信号处理函数已经不存在了，所以被捕获的信号需要恢复为
+    /// SIG_DFL，而被忽略的信号依然保持忽略。
+    pub fn reset_caught(&mut self) {
+        for action in self.table.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SignalAction::default();
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use super::SignalFlags;
+
+/// 按照真实时间计时，到期时发送 SIGALRM
+pub const ITIMER_REAL: usize = 0;
+/// 按照进程在用户态执行的时间计时，到期时发送 SIGVTALRM
+pub const ITIMER_VIRTUAL: usize = 1;
+/// 按照进程在用户态和内核态执行的时间计时，到期时发送 SIGPROF
+pub const ITIMER_PROF: usize = 2;
+
+const USEC_PER_SEC: usize = 1_000_000;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeVal {
+    pub sec: usize,
+    pub usec: usize,
+}
+
+impl TimeVal {
+    pub fn from_us(us: usize) -> Self {
+        Self {
+            sec: us / USEC_PER_SEC,
+            usec: us % USEC_PER_SEC,
+        }
+    }
+
+    /// 转换为微秒，usec 不合法或者溢出时返回 None
+    pub fn as_us(&self) -> Option<usize> {
+        if !self.is_valid() {
+            return None;
+        }
+        self.sec.checked_mul(USEC_PER_SEC)?.checked_add(self.usec)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.usec < USEC_PER_SEC
+    }
+}
+
+/// setitimer 和 getitimer 使用的定时器描述（struct itimerval）。
+/// value 为下一次到期前剩余的时间，为 0 表示定时器没有启用；
+/// interval 为到期后重新装载的时间，为 0 表示只触发一次。
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ITimerVal {
+    pub interval: TimeVal,
+    pub value: TimeVal,
+}
+
+// 内核中的定时器以微秒为单位。ITIMER_REAL 的 value 保存的是到期的绝对时间，
+// 其余两个保存的是剩余的执行时间。
+#[derive(Debug, Clone, Copy, Default)]
+struct IntervalTimer {
+    interval: usize,
+    value: usize,
+}
+
+/// 进程的三个间隔定时器
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IntervalTimers {
+    timers: [IntervalTimer; 3],
+}
+
+impl IntervalTimers {
+    /// 查询定时器 which 的当前状态，now 为当前时间（微秒）
+    pub fn get(&self, which: usize, now: usize) -> ITimerVal {
+        let timer = &self.timers[which];
+        let value = if which == ITIMER_REAL && timer.value != 0 {
+            // 已经到期但还没来得及处理时，剩余时间至少为 1 微秒，
+            // 否则会被当作没有启用的定时器
+            timer.value.saturating_sub(now).max(1)
+        } else {
+            timer.value
+        };
+        ITimerVal {
+            interval: TimeVal::from_us(timer.interval),
+            value: TimeVal::from_us(value),
+        }
+    }
+
+    /// 设置定时器 which，返回原来的状态。new 中的时间不合法或者到期时间溢出时
+    /// 返回 None，定时器保持不变。
+    pub fn set(&mut self, which: usize, new: &ITimerVal, now: usize) -> Option<ITimerVal> {
+        let interval = new.interval.as_us()?;
+        let mut value = new.value.as_us()?;
+        if which == ITIMER_REAL && value != 0 {
+            value = value.checked_add(now)?;
+        }
+        let old = self.get(which, now);
+        self.timers[which] = IntervalTimer { interval, value };
+        Some(old)
+    }
+
+    /// ITIMER_REAL 到期的绝对时间，没有启用时返回 None
+    pub fn real_deadline(&self) -> Option<usize> {
+        match self.timers[ITIMER_REAL].value {
+            0 => None,
+            deadline => Some(deadline),
+        }
+    }
+
+    /// 检查 ITIMER_REAL 是否已经到期，到期时按照 interval 重新装载
+    pub fn check_real(&mut self, now: usize) -> bool {
+        let timer = &mut self.timers[ITIMER_REAL];
+        if timer.value == 0 || now < timer.value {
+            return false;
+        }
+        timer.value = if timer.interval == 0 {
+            0
+        } else {
+            // 错过了多个周期时只触发一次
+            timer.value.saturating_add(timer.interval).max(now + 1)
+        };
+        true
+    }
+
+    /// 给进程计入 elapsed 微秒的执行时间，user 表示这段时间在用户态执行。
+    /// 返回到期的定时器对应的信号。
+    pub fn charge(&mut self, elapsed: usize, user: bool) -> SignalFlags {
+        let mut expired = SignalFlags::empty();
+        if user && Self::consume(&mut self.timers[ITIMER_VIRTUAL], elapsed) {
+            expired |= SignalFlags::SIGVTALRM;
+        }
+        if Self::consume(&mut self.timers[ITIMER_PROF], elapsed) {
+            expired |= SignalFlags::SIGPROF;
+        }
+        expired
+    }
+
+    fn consume(timer: &mut IntervalTimer, elapsed: usize) -> bool {
+        if timer.value == 0 {
+            return false;
+        }
+        if elapsed < timer.value {
+            timer.value -= elapsed;
+            return false;
+        }
+        timer.value = timer.interval;
+        true
+    }
+}
@@ -1,6 +1,7 @@
 use alloc::{
     collections::{BTreeMap, VecDeque},
     sync::Arc,
+    vec::Vec,
 };
 use lazy_static::*;
 
@@ -54,6 +55,20 @@ pub fn get_task_by_pid(pid: usize) -> Option<Arc<TaskControlBlock>> {
     PID_TO_TASK.exclusive_access().get(&pid).map(Arc::clone)
 }
 
+/// 与 get_task_by_pid 相同，但是 PID_TO_TASK 正在被访问时返回 None，
+/// 用于内核态 trap 等可能打断了其他访问的场合
+pub fn try_get_task_by_pid(pid: usize) -> Option<Arc<TaskControlBlock>> {
+    PID_TO_TASK
+        .try_exclusive_access()?
+        .get(&pid)
+        .map(Arc::clone)
+}
+
+/// 获取所有还没有退出的进程
+pub fn all_tasks() -> Vec<Arc<TaskControlBlock>> {
+    PID_TO_TASK.exclusive_access().values().cloned().collect()
+}
+
 /// 移除 pid 和 task control block 的映射关系
 pub fn remove_from_pid_to_task(pid: usize) {
     if PID_TO_TASK.exclusive_access().remove(&pid).is_none() {
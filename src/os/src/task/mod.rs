@@ -1,5 +1,6 @@
 mod action;
 mod context;
+mod itimer;
 pub mod manager;
 mod pid;
 pub mod processor;
@@ -11,25 +12,60 @@ use alloc::sync::Arc;
 use lazy_static::*;
 
 use crate::{
-    fs::{inode::OpenFlags, open_file},
+    fs::{inode::OpenFlags, open_file, File},
+    mm::{
+        address::{VirtAddr, VirtPageNum},
+        memory_set::{MapPermission, PageFaultError},
+        KERNEL_SPACE,
+    },
+    sync::UPSafeCell,
     task::task::TaskControlBlock,
+    timer,
 };
 
-pub use action::{SignalAction, SignalActions};
-pub use signal::{handle_signals, SignalFlags, MAX_SIG};
+pub use action::{SignalAction, SignalActionFlags, SignalActions};
+pub use itimer::{ITimerVal, IntervalTimers, ITIMER_PROF};
+pub use pid::{kernel_stack_lookup, kernel_stack_peak, kernel_stack_position};
+pub use signal::{
+    handle_signals, wait_for_signal, SignalFlags, SignalFrame, SignalInfo, SignalStack, MAX_SIG,
+    MINSIGSTKSZ, SIG_DFL, SIG_IGN, SS_DISABLE, SS_ONSTACK,
+};
 pub use {context::TaskContext, processor::run_tasks};
 
-use self::{manager::remove_from_pid_to_task, processor::current_task, task::TaskStatus};
+use self::{
+    manager::remove_from_pid_to_task,
+    processor::current_task,
+    signal::CLD_CONTINUED,
+    task::{TaskControlBlockInner, TaskStatus},
+};
 
 const INITPROC_NAME: &str = "initproc";
 
+/// waitpid 返回的状态：子进程被恢复，与 Linux 的编码一致
+pub const CONTINUED_STATUS: i32 = 0xffff;
+
+/// personality 标志：关闭地址空间布局随机化，与 Linux 的编码一致
+pub const ADDR_NO_RANDOMIZE: usize = 0x0040000;
+
+/// waitpid 返回的状态：子进程被信号 signum 暂停，与 Linux 的编码一致
+pub fn stopped_status(signum: usize) -> i32 {
+    ((signum as i32) << 8) | 0x7f
+}
+
 lazy_static! {
     pub static ref INITPROC: Arc<TaskControlBlock> = {
-        let initproc_data = open_file(INITPROC_NAME, OpenFlags::READ_ONLY)
+        let initproc_inode = open_file(INITPROC_NAME, OpenFlags::READ_ONLY)
             .unwrap()
-            .read_all();
-        Arc::new(TaskControlBlock::new(initproc_data.as_slice()))
+            .inode()
+            .unwrap();
+        Arc::new(TaskControlBlock::new(initproc_inode))
     };
+    // 换出用户页时 clock 算法的指针：下一次从进程 pid 的 vpn 处开始检查
+    static ref SWAP_CLOCK_HAND: UPSafeCell<(usize, VirtPageNum)> =
+        unsafe { UPSafeCell::new((0, VirtPageNum(0))) };
+    // 所有进程中最早到期的 ITIMER_REAL 的到期时间，没有启用的定时器时为 usize::MAX。
+    // 进程退出或者取消定时器时不更新，最多导致一次多余的检查。
+    static ref NEXT_REAL_DEADLINE: UPSafeCell<usize> = unsafe { UPSafeCell::new(usize::MAX) };
 }
 
 pub fn add_initproc() {
@@ -41,6 +77,7 @@ pub fn suspend_current_and_run_next() {
     let current_task = processor::take_current_task().unwrap();
     let mut current_task_inner = current_task.inner_exclusive_access();
     current_task_inner.task_status = TaskStatus::Ready;
+    charge_syscall_time(&mut current_task_inner);
     let current_task_cx_ptr = &mut current_task_inner.task_cx as *mut TaskContext;
     drop(current_task_inner);
 
@@ -48,6 +85,55 @@ pub fn suspend_current_and_run_next() {
     processor::schedule(current_task_cx_ptr);
 }
 
+// 暂停当前任务，任务离开就绪队列，直到 wake_stopped_task 把它重新放回
+pub fn stop_current_and_run_next() {
+    let current_task = processor::take_current_task().unwrap();
+    let mut current_task_inner = current_task.inner_exclusive_access();
+    current_task_inner.task_status = TaskStatus::Stopped;
+    charge_syscall_time(&mut current_task_inner);
+    let current_task_cx_ptr = &mut current_task_inner.task_cx as *mut TaskContext;
+    drop(current_task_inner);
+
+    // PID_TO_TASK 依然持有该任务，所以这里释放引用后任务不会被回收
+    drop(current_task);
+    processor::schedule(current_task_cx_ptr);
+}
+
+/// 收到 SIGCONT 或 SIGKILL 时唤醒被暂停的任务，任务重新进入就绪队列后
+/// 会在 handle_signals 中处理这些信号
+pub fn wake_stopped_task(task: Arc<TaskControlBlock>, flag: SignalFlags) {
+    let mut task_inner = task.inner_exclusive_access();
+    if flag == SignalFlags::SIGCONT && task_inner.frozen {
+        task_inner.frozen = false;
+        notify_parent(
+            task.getpid(),
+            &mut task_inner,
+            CONTINUED_STATUS,
+            CLD_CONTINUED,
+        );
+    }
+    if task_inner.task_status != TaskStatus::Stopped {
+        return;
+    }
+    task_inner.task_status = TaskStatus::Ready;
+    drop(task_inner);
+    manager::add_task(task);
+}
+
+/// 子进程被暂停或恢复，记录 waitpid 需要返回的状态并给 parent 发送 SIGCHLD
+pub fn notify_parent(pid: usize, task_inner: &mut TaskControlBlockInner, status: i32, code: i32) {
+    task_inner.wait_report = Some(status);
+    if let Some(parent) = task_inner
+        .parent
+        .as_ref()
+        .and_then(|parent| parent.upgrade())
+    {
+        parent
+            .inner_exclusive_access()
+            .add_signal(SignalFlags::SIGCHLD, SignalInfo::from_child(pid, code));
+    }
+}
+
 pub fn exit_current_and_run_next(exit_code: i32) {
     let current_task = processor::take_current_task().unwrap();
     let mut current_task_inner = current_task.inner_exclusive_access();
@@ -60,6 +146,7 @@ pub fn exit_current_and_run_next(exit_code: i32) {
     }
     current_task_inner.children.clear();
     current_task_inner.memory_set.release_areas();
+    current_task.kernel_stack.record_high_water_mark();
 
     remove_from_pid_to_task(current_task.getpid());
 
@@ -77,15 +164,229 @@ pub fn exit_current_and_run_next(exit_code: i32) {
 }
 
 /// 给当前进程添加一个信号
-pub fn current_add_signal(flag: SignalFlags) {
+pub fn current_add_signal(flag: SignalFlags, info: SignalInfo) {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.add_signal(flag, info);
+}
+
+/// 处理当前进程访问 va 时发生的缺页，返回 false 表示这是一次非法访问。
+/// 没有空闲的页框时交给 out_of_memory 处理，之后重新执行这条指令。
+pub fn current_handle_page_fault(va: VirtAddr, access: MapPermission) -> bool {
+    let task = current_task().unwrap();
+    let result = task
+        .inner_exclusive_access()
+        .memory_set
+        .handle_page_fault(va, access);
+    drop(task);
+    match result {
+        Ok(()) => true,
+        Err(PageFaultError::OutOfMemory) => {
+            out_of_memory();
+            true
+        }
+        Err(PageFaultError::Illegal) => false,
+    }
+}
+
+/// 换出页和丢弃页缓存之后依然无法处理缺页：选出驻留页最多的进程（initproc 除外）
+/// 发送 SIGKILL，它退出时释放的页框用来重新处理缺页。
+/// 被选中的不是当前进程时让出 CPU，让它有机会退出。
+pub fn out_of_memory() {
+    let current = current_task().unwrap();
+    let victim = manager::all_tasks()
+        .into_iter()
+        .filter(|task| !Arc::ptr_eq(task, &INITPROC))
+        .map(|task| {
+            let resident = task.inner_exclusive_access().memory_set.resident_pages();
+            (task, resident)
+        })
+        .max_by_key(|(_, resident)| *resident);
+    let (victim, resident) = match victim {
+        Some(victim) => victim,
+        None => panic!("Out of memory and no killable task!"),
+    };
+    let mut victim_inner = victim.inner_exclusive_access();
+    // 上一次选中的进程还没有退出时不再重复发送
+    if victim_inner.killed.is_none() && !victim_inner.signals.contains(SignalFlags::SIGKILL) {
+        println!(
+            "[kernel] Out of memory: killed task {} with {} resident pages",
+            victim.getpid(),
+            resident
+        );
+        victim_inner.add_signal(
+            SignalFlags::SIGKILL,
+            SignalInfo::from_kernel(SignalFlags::SIGKILL.signum(), 0),
+        );
+    }
+    drop(victim_inner);
+    if Arc::ptr_eq(&victim, &current) {
+        return;
+    }
+    drop(current);
+    wake_stopped_task(victim, SignalFlags::SIGKILL);
+    suspend_current_and_run_next();
+}
+
+/// 把最近没有被访问过的用户页换出到交换区，最多换出 count 个页，返回换出的页数。
+/// 所有进程的用户页组成一个环，clock 算法的指针在环上移动；inner 正在被访问的
+/// 进程（比如正在处理系统调用的当前进程）会被跳过。
+pub fn reclaim_frames(count: usize) -> usize {
+    // 读写交换区时需要通过内核地址空间翻译 DMA 地址
+    if KERNEL_SPACE.try_exclusive_access().is_none() {
+        return 0;
+    }
+    let tasks = manager::all_tasks();
+    if tasks.is_empty() {
+        return 0;
+    }
+    let mut hand = SWAP_CLOCK_HAND.exclusive_access();
+    let first = tasks
+        .iter()
+        .position(|task| task.getpid() >= hand.0)
+        .unwrap_or(0);
+    let mut freed = 0;
+    // 第一圈清除的访问位要到第二圈才能让页被换出，所以最多转两圈多
+    for i in 0..=2 * tasks.len() {
+        let task = &tasks[(first + i) % tasks.len()];
+        let start = if i == 0 && task.getpid() == hand.0 {
+            hand.1
+        } else {
+            VirtPageNum(0)
+        };
+        let mut task_inner = match task.try_inner_exclusive_access() {
+            Some(task_inner) => task_inner,
+            None => continue,
+        };
+        let (swapped, next) = task_inner.memory_set.swap_out_pages(start, count - freed);
+        freed += swapped;
+        if let Some(next) = next {
+            *hand = (task.getpid(), next);
+            return freed;
+        }
+        if freed == count {
+            *hand = (task.getpid() + 1, VirtPageNum(0));
+            return freed;
+        }
+    }
+    freed
+}
+
+/// 内核读取当前进程的用户字符串 ptr 之前先处理其中的缺页，返回 false 表示字符串不合法
+pub fn current_populate_str(ptr: usize) -> bool {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.memory_set.populate_str(ptr)
+}
+
+/// 当前进程是否有未被屏蔽的未决信号，阻塞在内核中的系统调用需要据此提前返回
+pub fn current_has_pending_signal() -> bool {
+    let task = current_task().unwrap();
+    let task_inner = task.inner_exclusive_access();
+    !(task_inner.signals - task_inner.signal_mask).is_empty()
+}
+
+/// 标记当前进程的系统调用被信号打断，由信号处理流程决定是否重新执行
+pub fn current_mark_syscall_interrupted() {
     let task = current_task().unwrap();
     let mut task_inner = task.inner_exclusive_access();
-    task_inner.signals.insert(flag);
+    task_inner.syscall_interrupted = true;
+}
+
+/// 时钟中断时把一个时钟周期计入当前进程在用户态执行的时间。内核运行时关闭了中断，
+/// 时钟中断只会发生在用户态，内核态的执行时间由 charge_syscall_time 计入。
+pub fn current_charge_tick() {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let expired = task_inner.itimers.charge(timer::USEC_PER_TICK, true);
+    add_itimer_signals(&mut task_inner, expired);
+}
+
+/// 当前进程开始执行系统调用，之后在内核中执行的时间计入 ITIMER_PROF
+pub fn current_enter_syscall() {
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().syscall_start = Some(timer::get_time_us());
+}
+
+/// 当前进程的系统调用返回，把还没有计入的内核态执行时间计入 ITIMER_PROF
+pub fn current_leave_syscall() {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    charge_syscall_time(&mut task_inner);
+    task_inner.syscall_start = None;
+}
+
+// 把系统调用从 syscall_start 到现在在内核中执行的时间计入 ITIMER_PROF。
+// 进程在系统调用中让出处理器时也会调用，被重新调度时 run_tasks 重新开始计时，
+// 这样等待的时间不会被计入。
+fn charge_syscall_time(task_inner: &mut TaskControlBlockInner) {
+    if let Some(start) = task_inner.syscall_start {
+        let now = timer::get_time_us();
+        let expired = task_inner.itimers.charge(now - start, false);
+        task_inner.syscall_start = Some(now);
+        add_itimer_signals(task_inner, expired);
+    }
+}
+
+fn add_itimer_signals(task_inner: &mut TaskControlBlockInner, expired: SignalFlags) {
+    for flag in [SignalFlags::SIGVTALRM, SignalFlags::SIGPROF] {
+        if expired.contains(flag) {
+            task_inner.add_signal(flag, SignalInfo::from_kernel(flag.signum(), 0));
+        }
+    }
+}
+
+/// 给进程组 pgid 中的全部进程发送信号
+pub fn send_signal_to_group(pgid: usize, flag: SignalFlags) {
+    for task in manager::all_tasks() {
+        let mut task_inner = task.inner_exclusive_access();
+        if task_inner.pgid == pgid {
+            task_inner.add_signal(flag, SignalInfo::from_kernel(flag.signum(), 0));
+        }
+    }
+}
+
+/// setitimer 启用 ITIMER_REAL 之后调用，deadline 为它到期的绝对时间
+pub fn arm_real_timer(deadline: usize) {
+    let mut next = NEXT_REAL_DEADLINE.exclusive_access();
+    *next = (*next).min(deadline);
+}
+
+/// 最早的 ITIMER_REAL 到期时检查所有进程，给到期的进程发送 SIGALRM，
+/// 并重新计算下一个到期时间
+pub fn check_real_timers() {
+    let now = timer::get_time_us();
+    if now < *NEXT_REAL_DEADLINE.exclusive_access() {
+        return;
+    }
+    let mut next = usize::MAX;
+    for task in manager::all_tasks() {
+        let mut task_inner = task.inner_exclusive_access();
+        if task_inner.itimers.check_real(now) {
+            task_inner.add_signal(
+                SignalFlags::SIGALRM,
+                SignalInfo::from_kernel(SignalFlags::SIGALRM.signum(), 0),
+            );
+        }
+        if let Some(deadline) = task_inner.itimers.real_deadline() {
+            next = next.min(deadline);
+        }
+    }
+    *NEXT_REAL_DEADLINE.exclusive_access() = next;
+}
+
+/// kernel stack 溢出时重新计算进程 pid 的最高水位并记录到它的 TCB 上，返回最高水位。
+/// PID_TO_TASK 正在被访问时无法找到 TCB，只计算不记录。
+pub fn update_kernel_stack_high_water_mark(pid: usize) -> usize {
+    match manager::try_get_task_by_pid(pid) {
+        Some(task) => task.kernel_stack.update_high_water_mark(),
+        None => pid::kernel_stack_high_water_mark(pid),
+    }
 }
 
-/// 返回特殊信号的 ID 和错误信息
+/// 如果当前进程被信号结束，返回退出码和错误信息
 pub fn check_signals_error_of_current() -> Option<(i32, &'static str)> {
     let task = current_task().unwrap();
     let task_inner = task.inner_exclusive_access();
-    task_inner.signals.check_error()
+    task_inner.killed.map(|signal| signal.check_error())
 }
@@ -4,6 +4,11 @@ use crate::{
     sync::UPSafeCell,
 };
 use alloc::vec::Vec;
+use core::{
+    mem::size_of,
+    slice,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 use lazy_static::*;
 
 pub struct PidHandle(pub usize);
@@ -58,20 +63,38 @@ pub fn pid_alloc() -> PidHandle {
     PID_ALLOCATOR.exclusive_access().alloc()
 }
 
+// kernel stack 创建时被填满 STACK_CANARY，从栈底开始第一个被改写的字就是栈用到的最深处
+const STACK_CANARY: usize = 0x5a5a_5a5a_5a5a_5a5a;
+
+// 所有已经退出的进程中 kernel stack 用到的最大字节数
+static KERNEL_STACK_PEAK: AtomicUsize = AtomicUsize::new(0);
+
 pub struct KernelStack {
     pid: usize,
+    // 最近一次记录的最高水位，在进程退出和 kernel stack 溢出时更新
+    high_water_mark: AtomicUsize,
 }
 
 impl KernelStack {
-    pub fn new(pid_handle: &PidHandle) -> Self {
+    // 没有空闲的页框时返回 None
+    pub fn new(pid_handle: &PidHandle) -> Option<Self> {
         let pid = pid_handle.0;
         let (bottom, top) = kernel_stack_position(pid);
-        KERNEL_SPACE.exclusive_access().insert_framed_area(
+        if !KERNEL_SPACE.exclusive_access().insert_framed_area(
             VirtAddr::from(bottom),
             VirtAddr::from(top),
             MapPermission::R | MapPermission::W,
-        );
-        KernelStack { pid: pid }
+        ) {
+            return None;
+        }
+        let words = unsafe {
+            slice::from_raw_parts_mut(bottom as *mut usize, (top - bottom) / size_of::<usize>())
+        };
+        words.fill(STACK_CANARY);
+        Some(KernelStack {
+            pid: pid,
+            high_water_mark: AtomicUsize::new(0),
+        })
     }
 
     #[allow(unused)]
@@ -91,6 +114,44 @@ impl KernelStack {
         let (_, top) = kernel_stack_position(self.pid);
         top
     }
+
+    // 重新计算最高水位并记录下来，返回记录的值
+    pub fn update_high_water_mark(&self) -> usize {
+        let used = kernel_stack_high_water_mark(self.pid);
+        self.high_water_mark.store(used, Ordering::Relaxed);
+        used
+    }
+
+    // 进程退出时记录它的最高水位，超过之前所有进程时打印出来
+    pub fn record_high_water_mark(&self) {
+        let used = self.update_high_water_mark();
+        if used > KERNEL_STACK_PEAK.fetch_max(used, Ordering::Relaxed) {
+            println!(
+                "[kernel] task {} used {} of {} bytes of kernel stack, the most so far",
+                self.pid,
+                used,
+                config::KERNEL_STACK_SIZE
+            );
+        }
+    }
+}
+
+// 进程 pid 的 kernel stack 的最高水位：创建以来用到的最大字节数
+pub fn kernel_stack_high_water_mark(pid: usize) -> usize {
+    let (bottom, top) = kernel_stack_position(pid);
+    let words = unsafe {
+        slice::from_raw_parts(bottom as *const usize, (top - bottom) / size_of::<usize>())
+    };
+    let untouched = words
+        .iter()
+        .take_while(|&&word| word == STACK_CANARY)
+        .count();
+    top - bottom - untouched * size_of::<usize>()
+}
+
+// 已经退出的进程中 kernel stack 的最高水位
+pub fn kernel_stack_peak() -> usize {
+    KERNEL_STACK_PEAK.load(Ordering::Relaxed)
 }
 
 impl Drop for KernelStack {
@@ -109,3 +170,18 @@ pub fn kernel_stack_position(pid: usize) -> (usize, usize) {
     let bottom = top - config::KERNEL_STACK_SIZE;
     (bottom, top)
 }
+
+// addr 位于某个 kernel stack 或者它下方的 guard page 时返回 pid，以及 addr 是否位于 guard page。
+// 只有 pid 的 kernel stack 已经被映射时才返回，KERNEL_SPACE 正在被访问时无法确认，返回 None。
+pub fn kernel_stack_lookup(addr: usize) -> Option<(usize, bool)> {
+    // 内核地址空间的高半部分只有跳板和 kernel stack
+    if addr < usize::MAX << 38 || addr >= config::TRAMPOLINE {
+        return None;
+    }
+    let pid = (config::TRAMPOLINE - 1 - addr) / (config::KERNEL_STACK_SIZE + config::PAGE_SIZE);
+    let (bottom, top) = kernel_stack_position(pid);
+    let mapped = !KERNEL_SPACE
+        .try_exclusive_access()?
+        .is_free(VirtAddr::from(bottom).into(), VirtAddr::from(top).into());
+    mapped.then_some((pid, addr < bottom))
+}
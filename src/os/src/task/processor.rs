@@ -1,6 +1,6 @@
 use alloc::sync::Arc;
 
-use crate::{sync::UPSafeCell, trap::TrapContext};
+use crate::{sync::UPSafeCell, timer, trap::TrapContext};
 
 use super::{
     context::TaskContext,
@@ -74,6 +74,10 @@ pub fn run_tasks() {
             let mut next_task_inner = next_task.inner_exclusive_access();
             let next_task_cx_ptr = &next_task_inner.task_cx as *const TaskContext;
             next_task_inner.task_status = TaskStatus::Running;
+            // 在系统调用中让出处理器的进程从现在开始重新计算内核态执行时间
+            if next_task_inner.syscall_start.is_some() {
+                next_task_inner.syscall_start = Some(timer::get_time_us());
+            }
             drop(next_task_inner);
             processor.current = Some(next_task);
             drop(processor);
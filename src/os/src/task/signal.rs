@@ -1,184 +1,525 @@
 use bitflags::*;
+use core::arch::global_asm;
 
-use super::{processor::current_task, suspend_current_and_run_next};
+use crate::{config::SIGRETURN_TRAMPOLINE, errno::EINTR};
 
-pub const MAX_SIG: usize = 31;
+use super::{
+    notify_parent, processor::current_task, stop_current_and_run_next, stopped_status,
+    suspend_current_and_run_next, SignalActionFlags,
+};
 
+global_asm!(include_str!("sigreturn.S"));
+
+pub const MAX_SIG: usize = 64;
+/// 实时信号的编号范围为 [SIGRTMIN, MAX_SIG]
+pub const SIGRTMIN: usize = 32;
+/// 每个进程最多可以排队的实时信号数量
+pub const MAX_QUEUED_SIGNALS: usize = 64;
+
+/// 特殊的信号处理函数：执行默认动作
+pub const SIG_DFL: usize = 0;
+/// 特殊的信号处理函数：忽略信号
+pub const SIG_IGN: usize = 1;
+
+// 信号编号从 1 开始，编号为 signum 的信号对应第 signum - 1 位，
+// 与 Linux 的 sigset_t 保持一致。
 bitflags! {
-    pub struct SignalFlags: u32 {
-        const SIGDEF = 1; // Default signal handling
-        const SIGHUP = 1 << 1;
-        const SIGINT = 1 << 2;
-        const SIGQUIT = 1 << 3;
-        const SIGILL = 1 << 4; // 指令异常
-        const SIGTRAP = 1 << 5;
-        const SIGABRT = 1 << 6;
-        const SIGBUS = 1 << 7;
-        const SIGFPE = 1 << 8;
-        const SIGKILL = 1 << 9; // 结束进程
-        const SIGUSR1 = 1 << 10;
-        const SIGSEGV = 1 << 11; // 内存段异常
-        const SIGUSR2 = 1 << 12;
-        const SIGPIPE = 1 << 13;
-        const SIGALRM = 1 << 14;
-        const SIGTERM = 1 << 15;
-        const SIGSTKFLT = 1 << 16;
-        const SIGCHLD = 1 << 17;
-        const SIGCONT = 1 << 18; // 恢复进程
-        const SIGSTOP = 1 << 19; // 暂停进程
-        const SIGTSTP = 1 << 20;
-        const SIGTTIN = 1 << 21;
-        const SIGTTOU = 1 << 22;
-        const SIGURG = 1 << 23;
-        const SIGXCPU = 1 << 24;
-        const SIGXFSZ = 1 << 25;
-        const SIGVTALRM = 1 << 26;
-        const SIGPROF = 1 << 27;
-        const SIGWINCH = 1 << 28;
-        const SIGIO = 1 << 29;
-        const SIGPWR = 1 << 30;
-        const SIGSYS = 1 << 31;
+    pub struct SignalFlags: u64 {
+        const SIGHUP = 1 << 0;
+        const SIGINT = 1 << 1;
+        const SIGQUIT = 1 << 2;
+        const SIGILL = 1 << 3; // 指令异常
+        const SIGTRAP = 1 << 4;
+        const SIGABRT = 1 << 5;
+        const SIGBUS = 1 << 6;
+        const SIGFPE = 1 << 7;
+        const SIGKILL = 1 << 8; // 结束进程
+        const SIGUSR1 = 1 << 9;
+        const SIGSEGV = 1 << 10; // 内存段异常
+        const SIGUSR2 = 1 << 11;
+        const SIGPIPE = 1 << 12;
+        const SIGALRM = 1 << 13;
+        const SIGTERM = 1 << 14;
+        const SIGSTKFLT = 1 << 15;
+        const SIGCHLD = 1 << 16;
+        const SIGCONT = 1 << 17; // 恢复进程
+        const SIGSTOP = 1 << 18; // 暂停进程
+        const SIGTSTP = 1 << 19;
+        const SIGTTIN = 1 << 20;
+        const SIGTTOU = 1 << 21;
+        const SIGURG = 1 << 22;
+        const SIGXCPU = 1 << 23;
+        const SIGXFSZ = 1 << 24;
+        const SIGVTALRM = 1 << 25;
+        const SIGPROF = 1 << 26;
+        const SIGWINCH = 1 << 27;
+        const SIGIO = 1 << 28;
+        const SIGPWR = 1 << 29;
+        const SIGSYS = 1 << 30;
+        // 全部实时信号 SIGRTMIN..=MAX_SIG
+        const SIGRT = 0xffff_ffff_8000_0000;
+    }
+}
+
+/// si_code: 信号由 kill 等系统调用发送
+pub const SI_USER: i32 = 0;
+/// si_code: 信号由内核产生，比如缺页异常
+pub const SI_KERNEL: i32 = 0x80;
+/// si_code: 信号由 sigqueue 发送
+pub const SI_QUEUE: i32 = -1;
+/// SIGCHLD 的 si_code: 子进程被暂停
+pub const CLD_STOPPED: i32 = 5;
+/// SIGCHLD 的 si_code: 子进程被恢复
+pub const CLD_CONTINUED: i32 = 6;
+
+/// 随信号一起递送给 SA_SIGINFO 信号处理函数的信息
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SignalInfo {
+    pub signo: i32,
+    pub code: i32,
+    // 发送信号的进程 pid
+    pub pid: usize,
+    // 导致异常的地址（SIGSEGV/SIGILL 等）
+    pub addr: usize,
+    // sigqueue 附带的数据，可以是整数也可以是指针
+    pub value: usize,
+}
+
+impl SignalInfo {
+    pub fn from_user(signum: usize, pid: usize) -> Self {
+        Self {
+            signo: signum as i32,
+            code: SI_USER,
+            pid,
+            addr: 0,
+            value: 0,
+        }
+    }
+
+    pub fn from_queue(signum: usize, pid: usize, value: usize) -> Self {
+        Self {
+            signo: signum as i32,
+            code: SI_QUEUE,
+            pid,
+            addr: 0,
+            value,
+        }
+    }
+
+    /// 子进程状态变化时发送给 parent 的 SIGCHLD，code 为 CLD_*
+    pub fn from_child(pid: usize, code: i32) -> Self {
+        Self {
+            signo: SignalFlags::SIGCHLD.signum() as i32,
+            code,
+            pid,
+            addr: 0,
+            value: 0,
+        }
+    }
+
+    pub fn from_kernel(signum: usize, addr: usize) -> Self {
+        Self {
+            signo: signum as i32,
+            code: SI_KERNEL,
+            pid: 0,
+            addr,
+            value: 0,
+        }
+    }
+}
+
+/// 被信号打断时用户程序的上下文：通用寄存器和 sepc。
+/// trap context 中的 sstatus 和内核栈、内核页表等内核字段不会被复制到用户栈上。
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SignalContext {
+    pub x: [usize; 32],
+    pub sepc: usize,
+}
+
+/// 递送信号时压入用户栈的信号帧，sigreturn 从这里恢复被打断的上下文。
+/// 信号帧之间通过 prev 串成一个链表，从而支持信号处理函数的嵌套。
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SignalFrame {
+    pub context: SignalContext,
+    // 递送信号前的信号掩码
+    pub old_mask: SignalFlags,
+    pub info: SignalInfo,
+    // 上一个信号帧的地址，0 表示没有
+    pub prev: usize,
+}
+
+/// ss_flags: 当前正在备用信号栈上执行
+pub const SS_ONSTACK: i32 = 1;
+/// ss_flags: 备用信号栈被禁用
+pub const SS_DISABLE: i32 = 2;
+/// 备用信号栈的最小长度
+pub const MINSIGSTKSZ: usize = 2048;
+
+/// 备用信号栈（stack_t），由 sigaltstack 设置。设置了 SA_ONSTACK 的信号处理
+/// 函数会在备用信号栈上执行，这样即使用户栈溢出也可以处理 SIGSEGV。
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SignalStack {
+    pub sp: usize,
+    pub flags: i32,
+    pub size: usize,
+}
+
+impl Default for SignalStack {
+    fn default() -> Self {
+        Self {
+            sp: 0,
+            flags: SS_DISABLE,
+            size: 0,
+        }
+    }
+}
+
+impl SignalStack {
+    pub fn is_enabled(&self) -> bool {
+        self.flags & SS_DISABLE == 0
+    }
+
+    /// 检查用户栈指针 sp 是否位于备用信号栈上
+    pub fn contains(&self, sp: usize) -> bool {
+        self.is_enabled() && sp > self.sp && sp <= self.sp + self.size
     }
 }
 
+/// 信号的默认动作，参见 signal(7)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignalDefaultAction {
+    // 结束进程
+    Terminate,
+    // 忽略信号
+    Ignore,
+    // 暂停进程
+    Stop,
+    // 恢复被暂停的进程
+    Continue,
+    // 结束进程并产生 core dump（目前不支持 core dump，效果与 Terminate 一致）
+    Core,
+}
+
 impl SignalFlags {
-    pub fn check_error(&self) -> Option<(i32, &'static str)> {
-        if self.contains(Self::SIGINT) {
-            Some((-2, "Killed, SIGINT=2"))
-        } else if self.contains(Self::SIGILL) {
-            Some((-4, "Illegal Instruction, SIGILL=4"))
-        } else if self.contains(Self::SIGABRT) {
-            Some((-6, "Aborted, SIGABRT=6"))
-        } else if self.contains(Self::SIGFPE) {
-            Some((-8, "Erroneous Arithmetic Operation, SIGFPE=8"))
-        } else if self.contains(Self::SIGKILL) {
-            Some((-9, "Killed, SIGKILL=9"))
-        } else if self.contains(Self::SIGSEGV) {
-            Some((-11, "Segmentation Fault, SIGSEGV=11"))
-        } else {
-            //println!("[K] signalflags check_error  {:?}", self);
-            None
+    /// 通过信号编号获取对应的信号，编号不合法时返回 None
+    pub fn from_signum(signum: usize) -> Option<Self> {
+        if signum == 0 || signum > MAX_SIG {
+            return None;
+        }
+        Self::from_bits(1 << (signum - 1))
+    }
+
+    /// 由用户传入的信号掩码构造，过滤非法位，同时 SIGKILL 和 SIGSTOP 不能被屏蔽
+    pub fn from_mask_bits(bits: u64) -> Self {
+        Self::from_bits_truncate(bits) - (Self::SIGKILL | Self::SIGSTOP)
+    }
+
+    /// 返回最低位信号的编号，只应该对单个信号调用
+    pub fn signum(&self) -> usize {
+        self.bits().trailing_zeros() as usize + 1
+    }
+
+    /// 实时信号会排队，不会因为重复发送而合并
+    pub fn is_realtime(&self) -> bool {
+        self.signum() >= SIGRTMIN
+    }
+
+    /// 返回单个信号的默认动作
+    pub fn default_action(&self) -> SignalDefaultAction {
+        match *self {
+            Self::SIGCHLD | Self::SIGURG | Self::SIGWINCH => SignalDefaultAction::Ignore,
+            Self::SIGSTOP | Self::SIGTSTP | Self::SIGTTIN | Self::SIGTTOU => {
+                SignalDefaultAction::Stop
+            }
+            Self::SIGCONT => SignalDefaultAction::Continue,
+            Self::SIGQUIT
+            | Self::SIGILL
+            | Self::SIGTRAP
+            | Self::SIGABRT
+            | Self::SIGBUS
+            | Self::SIGFPE
+            | Self::SIGSEGV
+            | Self::SIGXCPU
+            | Self::SIGXFSZ
+            | Self::SIGSYS => SignalDefaultAction::Core,
+            _ => SignalDefaultAction::Terminate,
         }
     }
+
+    /// 返回单个信号导致进程结束时的退出码和错误信息
+    pub fn check_error(&self) -> (i32, &'static str) {
+        let msg = match *self {
+            Self::SIGHUP => "Hangup, SIGHUP=1",
+            Self::SIGINT => "Interrupted, SIGINT=2",
+            Self::SIGQUIT => "Quit, SIGQUIT=3",
+            Self::SIGILL => "Illegal Instruction, SIGILL=4",
+            Self::SIGTRAP => "Trace/Breakpoint Trap, SIGTRAP=5",
+            Self::SIGABRT => "Aborted, SIGABRT=6",
+            Self::SIGBUS => "Bus Error, SIGBUS=7",
+            Self::SIGFPE => "Erroneous Arithmetic Operation, SIGFPE=8",
+            Self::SIGKILL => "Killed, SIGKILL=9",
+            Self::SIGUSR1 => "User Defined Signal 1, SIGUSR1=10",
+            Self::SIGSEGV => "Segmentation Fault, SIGSEGV=11",
+            Self::SIGUSR2 => "User Defined Signal 2, SIGUSR2=12",
+            Self::SIGPIPE => "Broken Pipe, SIGPIPE=13",
+            Self::SIGALRM => "Alarm Clock, SIGALRM=14",
+            Self::SIGTERM => "Terminated, SIGTERM=15",
+            Self::SIGSTKFLT => "Stack Fault, SIGSTKFLT=16",
+            Self::SIGXCPU => "CPU Time Limit Exceeded, SIGXCPU=24",
+            Self::SIGXFSZ => "File Size Limit Exceeded, SIGXFSZ=25",
+            Self::SIGVTALRM => "Virtual Timer Expired, SIGVTALRM=26",
+            Self::SIGPROF => "Profiling Timer Expired, SIGPROF=27",
+            Self::SIGIO => "I/O Possible, SIGIO=29",
+            Self::SIGPWR => "Power Failure, SIGPWR=30",
+            Self::SIGSYS => "Bad System Call, SIGSYS=31",
+            _ if self.is_realtime() => "Real-time Signal",
+            _ => "Killed by signal",
+        };
+        (-(self.signum() as i32), msg)
+    }
 }
 
-/// 执行内核的信号处理函数，理论上支持四个信号：
-/// - SIGKILL
-/// - SIGSTOP
-/// - SIGCONT
-/// - SIGDEF: 效果与 SIGKILL 一致
+/// 执行信号的默认动作
 fn call_kernel_signal_handler(signal: SignalFlags) {
     let task = current_task().unwrap();
     let mut task_inner = task.inner_exclusive_access();
-    match signal {
-        SignalFlags::SIGSTOP => {
-            if task_inner.signals.contains(SignalFlags::SIGSTOP) {
-                task_inner.frozen = true;
-                task_inner.signals ^= SignalFlags::SIGSTOP;
-            }
+    task_inner.take_signal(signal);
+    match signal.default_action() {
+        SignalDefaultAction::Terminate | SignalDefaultAction::Core => {
+            task_inner.killed = Some(signal);
         }
-        SignalFlags::SIGCONT => {
-            if task_inner.signals.contains(SignalFlags::SIGCONT) {
-                task_inner.frozen = false;
-                task_inner.signals ^= SignalFlags::SIGCONT;
-            }
+        SignalDefaultAction::Stop => {
+            task_inner.frozen = true;
+            notify_parent(
+                task.getpid(),
+                &mut task_inner,
+                stopped_status(signal.signum()),
+                CLD_STOPPED,
+            );
         }
-        _ => {
-            task_inner.killed = true;
+        SignalDefaultAction::Continue => {
+            task_inner.frozen = false;
         }
+        SignalDefaultAction::Ignore => {}
     }
 }
 
-/// 执行用户的信号处理函数
+/// 执行用户的信号处理函数：在用户栈上压入信号帧，修改 trap context 使得
+/// 返回用户态时进入信号处理函数，处理函数返回时经由 sigreturn 跳板恢复。
 fn call_user_signal_handler(sig: usize, flag: SignalFlags) {
     let task = current_task().unwrap();
     let mut task_inner = task.inner_exclusive_access();
 
-    let handler = task_inner.signal_actions.table[sig].handler;
-    if handler == 0 {
-        println!("[kernel] No user handler found for signal {}, do default action: ignore it or kill process", sig);
+    let action = task_inner.signal_actions.table[sig];
+    let info = task_inner
+        .take_signal(flag)
+        .unwrap_or(SignalInfo::from_kernel(sig, 0));
+    let trap_cx = task_inner.get_trap_cx();
+
+    // 被打断的系统调用此时已经回退到 ecall 指令，设置了 SA_RESTART 时保持不变，
+    // 信号处理函数返回后会重新执行系统调用，否则跳过 ecall 并返回 EINTR。
+    if task_inner.syscall_interrupted {
+        task_inner.syscall_interrupted = false;
+        if !action.flags.contains(SignalActionFlags::SA_RESTART) {
+            trap_cx.sepc += 4;
+            trap_cx.x[10] = (-EINTR) as usize;
+        }
+    }
+
+    // 设置了 SA_ONSTACK 且还没有在备用信号栈上时，切换到备用信号栈的栈顶
+    let mut sp = trap_cx.x[2];
+    let signal_stack = task_inner.signal_stack;
+    if action.flags.contains(SignalActionFlags::SA_ONSTACK)
+        && signal_stack.is_enabled()
+        && !signal_stack.contains(sp)
+    {
+        sp = signal_stack.sp + signal_stack.size;
+    }
+    // RISC-V 要求栈指针 16 字节对齐
+    let frame_ptr = sp.wrapping_sub(core::mem::size_of::<SignalFrame>()) & !0xf;
+    let frame = SignalFrame {
+        context: SignalContext {
+            x: trap_cx.x,
+            sepc: trap_cx.sepc,
+        },
+        // 在 sigsuspend 中被递送时，信号处理函数返回后恢复调用 sigsuspend 前的掩码
+        old_mask: task_inner.saved_mask.unwrap_or(task_inner.signal_mask),
+        info,
+        prev: task_inner.signal_frame,
+    };
+    if !task_inner
+        .memory_set
+        .copy_to_user(frame_ptr as *mut SignalFrame, &frame)
+    {
+        // 用户栈已经放不下信号帧了，只能结束进程
+        println!(
+            "[kernel] Failed to push signal frame for signal {}, kernel killed it.",
+            sig
+        );
+        task_inner.killed = Some(SignalFlags::SIGSEGV);
         return;
     }
+    task_inner.signal_frame = frame_ptr;
+    task_inner.saved_mask = None;
 
-    // 设置 task control block inner 与 signal 相关的字段
-    task_inner.signal_mask = task_inner.signal_actions.table[sig].mask;
-    task_inner.handling_sig = sig as isize;
-    task_inner.signals ^= flag;
-    let trap_ctx = task_inner.get_trap_cx();
-    // ref:
-    // https://kaisery.github.io/trpl-zh-cn/ch04-01-what-is-ownership.html#%E5%8F%98%E9%87%8F%E4%B8%8E%E6%95%B0%E6%8D%AE%E4%BA%A4%E4%BA%92%E7%9A%84%E6%96%B9%E5%BC%8F%E4%BA%8C%E5%85%8B%E9%9A%86
-    // TrapContext 实现了 `Copy` trait，一个旧的变量在将其**赋值**给其他变量后仍然可用。
-    task_inner.trap_ctx_backup = Some(*trap_ctx);
+    // 执行信号处理函数期间屏蔽 sa_mask 和当前信号
+    task_inner.signal_mask |= action.mask;
+    if !action.flags.contains(SignalActionFlags::SA_NODEFER) {
+        task_inner.signal_mask |= flag;
+    }
+    if action.flags.contains(SignalActionFlags::SA_RESETHAND) {
+        task_inner.signal_actions.table[sig] = Default::default();
+    }
+
+    trap_cx.sepc = action.handler;
+    trap_cx.set_sp(frame_ptr);
+    // 信号处理函数返回时跳转到 sigreturn 跳板
+    trap_cx.x[1] = SIGRETURN_TRAMPOLINE;
+    trap_cx.x[10] = sig;
+    if action.flags.contains(SignalActionFlags::SA_SIGINFO) {
+        let info_offset = &frame.info as *const _ as usize - &frame as *const _ as usize;
+        trap_cx.x[11] = frame_ptr + info_offset;
+        trap_cx.x[12] = frame_ptr;
+    }
+}
 
-    trap_ctx.sepc = handler;
-    trap_ctx.x[10] = sig;
+/// 按照信号的处理方式分发信号，返回 true 表示已经跳转到用户的信号处理函数
+fn dispatch_signal(sig: usize, flag: SignalFlags) -> bool {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    // 无论 SIGCONT 如何被处理，进程都会被恢复
+    if flag == SignalFlags::SIGCONT {
+        task_inner.frozen = false;
+    }
+    // SIGKILL 和 SIGSTOP 不能被捕获或忽略
+    let handler = if flag == SignalFlags::SIGKILL || flag == SignalFlags::SIGSTOP {
+        SIG_DFL
+    } else {
+        task_inner.signal_actions.table[sig].handler
+    };
+    match handler {
+        SIG_DFL => {
+            drop(task_inner);
+            drop(task);
+            call_kernel_signal_handler(flag);
+            false
+        }
+        SIG_IGN => {
+            task_inner.take_signal(flag);
+            false
+        }
+        _ => {
+            drop(task_inner);
+            drop(task);
+            call_user_signal_handler(sig, flag);
+            true
+        }
+    }
 }
 
 pub fn check_pending_signals() {
-    for sig in 0..(MAX_SIG + 1) {
+    for sig in 1..(MAX_SIG + 1) {
         let task = current_task().unwrap();
         let task_inner = task.inner_exclusive_access();
-        let flag = SignalFlags::from_bits(1 << sig).unwrap();
+        let flag = SignalFlags::from_signum(sig).unwrap();
         // 当前没有该信号或者该信号被屏蔽
         if !task_inner.signals.contains(flag) || task_inner.signal_mask.contains(flag) {
             continue;
         }
-        if task_inner.handling_sig == -1 {
-            // ===== 当前没有正在处理的信号 =====
-            drop(task_inner);
-            drop(task);
-            if flag == SignalFlags::SIGKILL
-                || flag == SignalFlags::SIGSTOP
-                || flag == SignalFlags::SIGCONT
-                || flag == SignalFlags::SIGDEF
-            {
-                call_kernel_signal_handler(flag);
-            } else {
-                call_user_signal_handler(sig, flag);
-                // 为什么用户的处理程序有个 return 呢？
-                // 我考虑了下因为用户的处理程序需要陷入用户的程序执行，只有立即
-                // return 才能顺利执行 trap_handler 函数。
-                return;
-            }
-        } else {
-            // ===== 当前有正在处理的信号 =====
-            // 检查当前信号是否被正在执行的信号屏蔽
-            if !task_inner.signal_actions.table[task_inner.handling_sig as usize]
-                .mask
-                .contains(flag)
-            {
-                drop(task_inner);
-                drop(task);
-                if flag == SignalFlags::SIGKILL
-                    || flag == SignalFlags::SIGSTOP
-                    || flag == SignalFlags::SIGCONT
-                    || flag == SignalFlags::SIGDEF
-                {
-                    call_kernel_signal_handler(flag);
-                } else {
-                    call_user_signal_handler(sig, flag);
-                    // return 原因同上
-                    return;
-                }
-            }
+        drop(task_inner);
+        drop(task);
+        if dispatch_signal(sig, flag) {
+            // 为什么用户的处理程序有个 return 呢？
+            // 我考虑了下因为用户的处理程序需要陷入用户的程序执行，只有立即
+            // return 才能顺利执行 trap_handler 函数。
+            return;
         }
     }
 }
 
-/// 处理信号，如果进程被暂停则会持续等待
+/// 处理信号，如果进程被暂停则离开就绪队列，直到收到 SIGCONT 或 SIGKILL
 pub fn handle_signals() {
-    check_pending_signals();
     loop {
+        check_pending_signals();
         let task = current_task().unwrap();
         let task_inner = task.inner_exclusive_access();
         let frozen_flag = task_inner.frozen;
-        let killed_flag = task_inner.killed;
+        let killed_flag = task_inner.killed.is_some();
         drop(task_inner);
         drop(task);
         if (!frozen_flag) || killed_flag {
             break;
         }
-        check_pending_signals();
-        suspend_current_and_run_next();
+        stop_current_and_run_next();
+    }
+    // 没有进入用户的信号处理函数（信号被忽略或者执行了默认动作），
+    // 被打断的系统调用直接重新执行
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.syscall_interrupted = false;
+    // sigsuspend 等到的信号没有进入用户的信号处理函数，直接恢复原来的掩码
+    if let Some(mask) = task_inner.saved_mask.take() {
+        task_inner.signal_mask = mask;
+    }
+}
+
+// sigsuspend 等待期间处理未屏蔽的未决信号：被忽略的信号直接丢弃，默认动作为暂停的信号
+// 使进程暂停。返回 true 表示有信号会进入用户的信号处理函数或者结束进程。
+fn check_suspend_signals() -> bool {
+    for sig in 1..(MAX_SIG + 1) {
+        let task = current_task().unwrap();
+        let mut task_inner = task.inner_exclusive_access();
+        let flag = SignalFlags::from_signum(sig).unwrap();
+        if !task_inner.signals.contains(flag) || task_inner.signal_mask.contains(flag) {
+            continue;
+        }
+        if flag == SignalFlags::SIGCONT {
+            task_inner.frozen = false;
+        }
+        let handler = if flag == SignalFlags::SIGKILL || flag == SignalFlags::SIGSTOP {
+            SIG_DFL
+        } else {
+            task_inner.signal_actions.table[sig].handler
+        };
+        match handler {
+            SIG_IGN => {
+                task_inner.take_signal(flag);
+            }
+            SIG_DFL => match flag.default_action() {
+                SignalDefaultAction::Ignore | SignalDefaultAction::Continue => {
+                    task_inner.take_signal(flag);
+                }
+                SignalDefaultAction::Stop => {
+                    drop(task_inner);
+                    drop(task);
+                    call_kernel_signal_handler(flag);
+                }
+                SignalDefaultAction::Terminate | SignalDefaultAction::Core => return true,
+            },
+            _ => return true,
+        }
+    }
+    false
+}
+
+/// sigsuspend 使用：等待一个会进入用户的信号处理函数或者结束进程的信号。
+/// 被忽略的信号不会唤醒进程；进程被暂停时离开就绪队列，恢复后继续等待。
+pub fn wait_for_signal() {
+    loop {
+        if check_suspend_signals() {
+            return;
+        }
+        let task = current_task().unwrap();
+        let frozen = task.inner_exclusive_access().frozen;
+        drop(task);
+        if frozen {
+            stop_current_and_run_next();
+        } else {
+            suspend_current_and_run_next();
+        }
     }
 }
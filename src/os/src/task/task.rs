@@ -1,14 +1,19 @@
 use core::cell::RefMut;
 
 use alloc::{
+    collections::{BTreeMap, VecDeque},
     string::String,
     sync::{Arc, Weak},
     vec::Vec,
 };
 
+use easy_fs::Inode;
+
 use super::{
     pid::{self, KernelStack, PidHandle},
-    SignalActions, SignalFlags, TaskContext,
+    signal::MAX_QUEUED_SIGNALS,
+    IntervalTimers, SignalActions, SignalFlags, SignalInfo, SignalStack, TaskContext,
+    ADDR_NO_RANDOMIZE, MAX_SIG,
 };
 
 use crate::{
@@ -17,7 +22,8 @@ use crate::{
     mm::{
         self,
         address::{PhysPageNum, VirtAddr},
-        memory_set::MemorySet,
+        elf::{AuxHeader, AT_NULL},
+        memory_set::{LoadError, MapPermission, MemorySet},
         page_table::translated_ref_mut,
         KERNEL_SPACE,
     },
@@ -44,17 +50,37 @@ pub struct TaskControlBlockInner {
     pub children: Vec<Arc<TaskControlBlock>>,
 
     pub exit_code: i32,
+    // 进程组，控制台的控制字符产生的信号会发送给前台进程组中的全部进程
+    pub pgid: usize,
 
     pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
 
     // ===== signal-related =====
     pub signals: SignalFlags,
+    // 未决信号附带的信息，以信号编号为 key。普通信号最多只有一个，
+    // 实时信号按照发送顺序排队。
+    pub signal_infos: BTreeMap<usize, VecDeque<SignalInfo>>,
     pub signal_mask: SignalFlags,
-    pub handling_sig: isize,
+    // sigsuspend 临时替换掉的信号掩码，信号递送后恢复
+    pub saved_mask: Option<SignalFlags>,
     pub signal_actions: SignalActions,
-    pub killed: bool,
+    // 导致进程结束的信号
+    pub killed: Option<SignalFlags>,
     pub frozen: bool,
-    pub trap_ctx_backup: Option<TrapContext>,
+    // 暂停或恢复后等待 parent 通过 waitpid 获取的状态，获取后清空
+    pub wait_report: Option<i32>,
+    // 最内层信号帧在用户栈上的地址，0 表示当前没有在执行信号处理函数
+    pub signal_frame: usize,
+    // sigaltstack 设置的备用信号栈
+    pub signal_stack: SignalStack,
+    // 刚刚执行的系统调用被信号打断，trap context 已经回退到 ecall 指令
+    pub syscall_interrupted: bool,
+    // setitimer 设置的间隔定时器
+    pub itimers: IntervalTimers,
+    // 正在执行的系统调用在内核中开始计时的时间，None 表示没有在执行系统调用
+    pub syscall_start: Option<usize>,
+    // personality 设置的执行域标志，fork 时继承，exec 时保持不变
+    pub personality: usize,
 }
 
 impl TaskControlBlockInner {
@@ -78,6 +104,59 @@ impl TaskControlBlockInner {
             self.fd_table.len() - 1
         }
     }
+    // 添加一个未决信号。普通信号在递送前重复发送会被合并，只保留第一次发送时
+    // 的信息；实时信号则会排队，队列已满时返回 false。
+    pub fn add_signal(&mut self, flag: SignalFlags, info: SignalInfo) -> bool {
+        // 产生 SIGCONT 时丢弃未决的暂停信号，产生暂停信号时丢弃未决的 SIGCONT
+        let stop_signals = SignalFlags::SIGSTOP
+            | SignalFlags::SIGTSTP
+            | SignalFlags::SIGTTIN
+            | SignalFlags::SIGTTOU;
+        if flag == SignalFlags::SIGCONT {
+            self.discard_signals(stop_signals);
+        } else if stop_signals.contains(flag) {
+            self.discard_signals(SignalFlags::SIGCONT);
+        }
+        if flag.is_realtime() {
+            let queued: usize = self.signal_infos.values().map(|queue| queue.len()).sum();
+            if queued >= MAX_QUEUED_SIGNALS {
+                return false;
+            }
+        }
+        let queue = self.signal_infos.entry(flag.signum()).or_default();
+        if flag.is_realtime() || queue.is_empty() {
+            queue.push_back(info);
+        }
+        self.signals.insert(flag);
+        true
+    }
+    // 丢弃 flags 中全部未决信号及其排队的信息
+    pub fn discard_signals(&mut self, flags: SignalFlags) {
+        for signum in 1..=MAX_SIG {
+            let flag = SignalFlags::from_signum(signum).unwrap();
+            if flags.contains(flag) {
+                self.signal_infos.remove(&signum);
+            }
+        }
+        self.signals.remove(flags);
+    }
+    // 取出一个即将被递送的信号的信息，队列中没有更多该信号时清除未决位
+    pub fn take_signal(&mut self, flag: SignalFlags) -> Option<SignalInfo> {
+        let signum = flag.signum();
+        let info = self
+            .signal_infos
+            .get_mut(&signum)
+            .and_then(|queue| queue.pop_front());
+        if self
+            .signal_infos
+            .get(&signum)
+            .map_or(true, |queue| queue.is_empty())
+        {
+            self.signal_infos.remove(&signum);
+            self.signals.remove(flag);
+        }
+        info
+    }
 }
 
 impl TaskControlBlock {
@@ -85,9 +164,14 @@ impl TaskControlBlock {
         self.inner.exclusive_access()
     }
 
+    pub fn try_inner_exclusive_access(&self) -> Option<RefMut<'_, TaskControlBlockInner>> {
+        self.inner.try_exclusive_access()
+    }
+
     // new 读取用户 elf 程序，创建用户空间同时初始化 kernel stack
-    pub fn new(elf_data: &[u8]) -> Self {
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+    pub fn new(elf_inode: Arc<Inode>) -> Self {
+        let (memory_set, user_sp, entry_point, _) =
+            MemorySet::from_elf(elf_inode, true).expect("failed to load initproc");
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(config::TRAP_CONTEXT).into())
             .unwrap()
@@ -95,7 +179,7 @@ impl TaskControlBlock {
         let task_status = TaskStatus::Ready;
 
         let pid_handle = pid::pid_alloc();
-        let kernel_stack = pid::KernelStack::new(&pid_handle);
+        let kernel_stack = pid::KernelStack::new(&pid_handle).expect("out of memory");
         let kernel_stack_top = kernel_stack.get_top();
         let task_cx_block_inner = unsafe {
             UPSafeCell::new(TaskControlBlockInner {
@@ -107,6 +191,8 @@ impl TaskControlBlock {
                 parent: None,
                 children: Vec::new(),
                 exit_code: 0,
+                // initproc 自成一个进程组
+                pgid: pid_handle.0,
                 fd_table: vec![
                     // 0 -> stdin
                     Some(Arc::new(Stdin)),
@@ -116,12 +202,19 @@ impl TaskControlBlock {
                     Some(Arc::new(Stdout)),
                 ],
                 signals: SignalFlags::empty(),
+                signal_infos: BTreeMap::new(),
                 signal_mask: SignalFlags::empty(),
-                handling_sig: -1,
+                saved_mask: None,
                 signal_actions: SignalActions::default(),
-                killed: false,
+                killed: None,
                 frozen: false,
-                trap_ctx_backup: None,
+                wait_report: None,
+                signal_frame: 0,
+                signal_stack: SignalStack::default(),
+                syscall_interrupted: false,
+                itimers: IntervalTimers::default(),
+                syscall_start: None,
+                personality: 0,
             })
         };
 
@@ -148,15 +241,16 @@ impl TaskControlBlock {
         self.pid.0
     }
 
-    pub fn fork(self: &Arc<TaskControlBlock>) -> Arc<TaskControlBlock> {
+    // 复制出一个子进程，没有空闲的页框时返回 None
+    pub fn fork(self: &Arc<TaskControlBlock>) -> Option<Arc<TaskControlBlock>> {
         let mut parent_inner = self.inner_exclusive_access();
 
         let pid_handle = pid::pid_alloc();
-        let kernel_stack = pid::KernelStack::new(&pid_handle);
+        let kernel_stack = pid::KernelStack::new(&pid_handle)?;
         let kernel_stack_top = kernel_stack.get_top();
 
         // tcb inner
-        let memory_set = MemorySet::from_existed_user(&parent_inner.memory_set);
+        let memory_set = MemorySet::from_existed_user(&parent_inner.memory_set)?;
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(config::TRAP_CONTEXT).into())
             .unwrap()
@@ -179,16 +273,27 @@ impl TaskControlBlock {
             parent: Some(Arc::downgrade(self)),
             children: Vec::new(),
             exit_code: 0,
+            // 子进程与 parent 属于同一个进程组
+            pgid: parent_inner.pgid,
             fd_table: new_fd_table,
             signals: SignalFlags::empty(),
+            signal_infos: BTreeMap::new(),
             // 继承 parent 的信号掩码
             signal_mask: parent_inner.signal_mask,
-            handling_sig: -1,
+            saved_mask: None,
             // 继承 parent 的 signal actions
             signal_actions: parent_inner.signal_actions.clone(),
-            killed: false,
+            killed: None,
             frozen: false,
-            trap_ctx_backup: None,
+            wait_report: None,
+            // 用户栈被完整复制，信号帧也随之被复制
+            signal_frame: parent_inner.signal_frame,
+            signal_stack: parent_inner.signal_stack,
+            syscall_interrupted: false,
+            // 子进程不继承 parent 的定时器
+            itimers: IntervalTimers::default(),
+            syscall_start: None,
+            personality: parent_inner.personality,
         };
 
         let tcb = Arc::new(TaskControlBlock {
@@ -202,17 +307,41 @@ impl TaskControlBlock {
         let mut trap_cx = tcb.inner_exclusive_access().get_trap_cx();
         trap_cx.kernel_sp = kernel_stack_top;
 
-        tcb
+        Some(tcb)
     }
 
-    pub fn exec(&self, elf_data: &[u8], args: Vec<String>) {
-        let (mmset, mut user_sp, entrypoint) = MemorySet::from_elf(elf_data);
+    // 用 elf_inode 中的程序替换当前的地址空间。elf 文件不合法或者没有空闲的页框时
+    // 返回对应的错误，原来的地址空间保持不变。
+    pub fn exec(&self, elf_inode: Arc<Inode>, args: Vec<String>) -> Result<(), LoadError> {
+        let randomize = self.inner_exclusive_access().personality & ADDR_NO_RANDOMIZE == 0;
+        let (mut mmset, mut user_sp, entrypoint, mut auxv) =
+            MemorySet::from_elf(elf_inode, randomize)?;
+        auxv.push(AuxHeader::new(AT_NULL, 0));
 
         let trap_cx_ppn = mmset
             .translate(VirtAddr::from(config::TRAP_CONTEXT).into())
             .unwrap()
             .ppn();
 
+        // 用户栈的页在第一次被访问时才分配，先处理参数和辅助向量所在的页
+        let auxv_size = auxv.len() * core::mem::size_of::<AuxHeader>();
+        let args_size = auxv_size
+            + (args.len() + 1) * core::mem::size_of::<usize>()
+            + args.iter().map(|arg| arg.len() + 1).sum::<usize>();
+        mmset
+            .populate(user_sp - args_size, args_size, MapPermission::W)
+            .map_err(|_| LoadError::OutOfMemory)?;
+
+        // 辅助向量紧跟在 argv 的结尾之后
+        user_sp -= auxv_size;
+        let auxv_base = user_sp;
+        for (i, aux) in auxv.iter().enumerate() {
+            *translated_ref_mut(
+                mmset.token(),
+                (auxv_base + i * core::mem::size_of::<AuxHeader>()) as *mut AuxHeader,
+            ) = *aux;
+        }
+
         // push args on user sp
         user_sp -= (args.len() + 1) * core::mem::size_of::<usize>();
         let argv_base = user_sp;
@@ -247,6 +376,10 @@ impl TaskControlBlock {
         let mut tcb_inner = self.inner_exclusive_access();
         tcb_inner.memory_set = mmset;
         tcb_inner.trap_cx_ppn = trap_cx_ppn;
+        // 被捕获的信号恢复为默认处理方式，信号掩码和未决信号保持不变
+        tcb_inner.signal_actions.reset_caught();
+        tcb_inner.signal_frame = 0;
+        tcb_inner.signal_stack = SignalStack::default();
         let mut trap_cx = TrapContext::app_init_context(
             entrypoint,
             user_sp,
@@ -256,7 +389,9 @@ impl TaskControlBlock {
         );
         trap_cx.x[10] = args.len();
         trap_cx.x[11] = argv_base;
+        trap_cx.x[12] = auxv_base;
         *tcb_inner.get_trap_cx() = trap_cx;
+        Ok(())
     }
 }
 
@@ -264,5 +399,7 @@ impl TaskControlBlock {
 pub enum TaskStatus {
     Ready,
     Running,
+    // 被 SIGSTOP 等信号暂停，不在就绪队列中，直到收到 SIGCONT 或 SIGKILL
+    Stopped,
     Zombie,
 }
@@ -1,18 +1,26 @@
-use crate::{config, sbi};
+use crate::{drivers::fdt, sbi};
 use riscv::register::time;
 
 const TICKS_PER_SEC: usize = 100;
 const MSEC_PER_SEC: usize = 1000;
+const USEC_PER_SEC: usize = 1_000_000;
+// 每个时钟中断周期的长度（微秒）
+pub const USEC_PER_TICK: usize = USEC_PER_SEC / TICKS_PER_SEC;
 
 pub fn get_time() -> usize {
     time::read()
 }
 
 pub fn get_time_ms() -> usize {
-    time::read() / (config::CLOCK_FREQ / MSEC_PER_SEC)
+    time::read() / (fdt::timebase_frequency() / MSEC_PER_SEC)
+}
+
+pub fn get_time_us() -> usize {
+    // 时钟频率不一定能被 USEC_PER_SEC 整除，所以先乘后除
+    time::read() * USEC_PER_SEC / fdt::timebase_frequency()
 }
 
 // time interrupt will be fired every 10ms
 pub fn set_next_trigger() {
-    sbi::set_timer(get_time() + config::CLOCK_FREQ / TICKS_PER_SEC);
+    sbi::set_timer(get_time() + fdt::timebase_frequency() / TICKS_PER_SEC);
 }
@@ -2,10 +2,13 @@ mod context;
 
 use crate::{
     config,
-    syscall::syscall,
+    errno::ERESTARTSYS,
+    fs,
+    mm::{frame_allocator, memory_set::MapPermission},
+    syscall::{syscall, SYSCALL_SIGRETURN},
     task::{
         self, check_signals_error_of_current, exit_current_and_run_next, handle_signals, processor,
-        SignalFlags,
+        SignalFlags, SignalInfo,
     },
     timer,
 };
@@ -13,7 +16,7 @@ use core::arch::{asm, global_asm};
 use riscv::register::{
     mtvec::TrapMode,
     scause::{self, Exception, Interrupt, Trap},
-    sie, stval, stvec,
+    sepc, sie, stval, stvec,
 };
 
 pub use context::TrapContext;
@@ -25,8 +28,11 @@ pub fn init() {
 }
 
 fn set_kernel_trap_entry() {
+    extern "C" {
+        fn __kerneltrap();
+    }
     unsafe {
-        stvec::write(trap_from_kernel as usize, TrapMode::Direct);
+        stvec::write(__kerneltrap as usize, TrapMode::Direct);
     }
 }
 
@@ -48,32 +54,77 @@ pub fn trap_handler() -> ! {
     set_kernel_trap_entry();
     let scause = scause::read(); // trap 原因
     let stval = stval::read(); // trap 附加信息
+
+    // 此时没有持有任何进程的 inner，当前进程的页也可以被换出。
+    // 换出之后依然凑不够页框时由 OOM killer 结束驻留页最多的进程，再处理这次 trap
+    if !frame_allocator::frame_reserve(config::RESERVED_FRAMES) {
+        task::out_of_memory();
+    }
     match scause.cause() {
         Trap::Exception(Exception::UserEnvCall) => {
             let mut trap_cx = processor::current_trap_cx();
             // sepc 目前指向的是 ecall 指令的地址，但是它应该指向的是下一条指令，
             // 已知 ecall 指令的长度为 4，所以这里需要加 4。
             trap_cx.sepc += 4;
-            let result =
-                syscall(trap_cx.x[17], [trap_cx.x[10], trap_cx.x[11], trap_cx.x[12]]) as usize;
+            let args = [
+                trap_cx.x[10],
+                trap_cx.x[11],
+                trap_cx.x[12],
+                trap_cx.x[13],
+                trap_cx.x[14],
+                trap_cx.x[15],
+            ];
+            let syscall_id = trap_cx.x[17];
+            task::current_enter_syscall();
+            let result = syscall(syscall_id, args);
+            task::current_leave_syscall();
             // trap_cx 在执行 `exec` 被执行后会被回收，
             // 所以这里需要重新获取一个新的 `trap_cx`。
             trap_cx = processor::current_trap_cx();
-            trap_cx.x[10] = result
+            if result == -ERESTARTSYS && syscall_id != SYSCALL_SIGRETURN {
+                // 系统调用被信号打断，先回退到 ecall 指令并恢复参数，
+                // 由 handle_signals 决定重新执行还是返回 EINTR。
+                // sigreturn 返回的是恢复出来的 a0，它恰好等于 -ERESTARTSYS 时也要原样写回
+                trap_cx.sepc -= 4;
+                trap_cx.x[10] = args[0];
+                task::current_mark_syscall_interrupted();
+            } else {
+                trap_cx.x[10] = result as usize;
+            }
         }
+        // 缺页已经处理时回到用户态重新执行这条指令，否则是一次非法访问。
+        // 没有空闲的页框时 OOM killer 结束驻留页最多的进程之后再重新执行。
+        Trap::Exception(Exception::StorePageFault)
+            if task::current_handle_page_fault(stval.into(), MapPermission::W) => {}
+        Trap::Exception(Exception::LoadPageFault)
+            if task::current_handle_page_fault(stval.into(), MapPermission::R) => {}
+        Trap::Exception(Exception::InstructionPageFault)
+            if task::current_handle_page_fault(stval.into(), MapPermission::X) => {}
         Trap::Exception(Exception::StoreFault)
         | Trap::Exception(Exception::StorePageFault)
         | Trap::Exception(Exception::LoadFault)
-        | Trap::Exception(Exception::LoadPageFault) => {
+        | Trap::Exception(Exception::LoadPageFault)
+        | Trap::Exception(Exception::InstructionPageFault) => {
             println!("[kernel] PageFault in application, kernel killed it.");
-            task::current_add_signal(SignalFlags::SIGSEGV);
+            task::current_add_signal(
+                SignalFlags::SIGSEGV,
+                SignalInfo::from_kernel(SignalFlags::SIGSEGV.signum(), stval),
+            );
         }
         Trap::Exception(Exception::IllegalInstruction) => {
             println!("[kernel] IllegalInstruction in application, kernel killed it.");
-            task::current_add_signal(SignalFlags::SIGILL);
+            task::current_add_signal(
+                SignalFlags::SIGILL,
+                SignalInfo::from_kernel(SignalFlags::SIGILL.signum(), stval),
+            );
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
             timer::set_next_trigger();
+            task::current_charge_tick();
+            task::check_real_timers();
+            // 正在执行的进程可能从不读取 stdin，所以在时钟中断中处理控制台输入，
+            // 这样 Ctrl-C 等控制字符才能及时生效
+            fs::poll_console_input();
             task::suspend_current_and_run_next();
         }
         _ => {
@@ -118,7 +169,93 @@ pub fn trap_return() -> ! {
     }
 }
 
+// 内核态的 trap 在 __kerneltrap 中切换到 emergency stack 之后来到这里，
+// sp、ra 和 fp 是发生 trap 时的寄存器。内核态的 trap 都是无法恢复的错误，
+// 打印出错的位置和调用栈之后 panic。
 #[no_mangle]
-pub fn trap_from_kernel() -> ! {
+pub extern "C" fn trap_from_kernel(sp: usize, ra: usize, fp: usize) -> ! {
+    let scause = scause::read();
+    let stval = stval::read();
+    let sepc = sepc::read();
+    // 访问 guard page 说明 kernel stack 溢出了
+    if let Some((pid, true)) = task::kernel_stack_lookup(stval) {
+        println!(
+            "[kernel] kernel stack overflow in task {}: {:?} at {:#x}, sp = {:#x}, sepc = {:#x}",
+            pid,
+            scause.cause(),
+            stval,
+            sp,
+            sepc
+        );
+        println!(
+            "[kernel] task {} used {} of {} bytes of kernel stack before the overflow",
+            pid,
+            task::update_kernel_stack_high_water_mark(pid),
+            config::KERNEL_STACK_SIZE
+        );
+        println!(
+            "[kernel] the deepest kernel stack of exited tasks used {} of {} bytes",
+            task::kernel_stack_peak(),
+            config::KERNEL_STACK_SIZE
+        );
+        print_backtrace(sepc, ra, fp, task::kernel_stack_position(pid));
+        panic!("kernel stack overflow in task {}", pid);
+    }
+    println!(
+        "[kernel] {:?} in kernel, stval = {:#x}, sp = {:#x}, sepc = {:#x}",
+        scause.cause(),
+        stval,
+        sp,
+        sepc
+    );
+    if let Some(stack) = kernel_stack_range(sp) {
+        print_backtrace(sepc, ra, fp, stack);
+    }
     panic!("a trap from kernel")
 }
+
+// sp 所在的内核栈的范围：某个进程的 kernel stack（包括溢出到的 guard page），
+// 或者启动时使用的 boot stack
+fn kernel_stack_range(sp: usize) -> Option<(usize, usize)> {
+    extern "C" {
+        fn boot_stack();
+        fn boot_stack_top();
+    }
+    if let Some((pid, _)) = task::kernel_stack_lookup(sp) {
+        return Some(task::kernel_stack_position(pid));
+    }
+    if sp >= boot_stack as usize && sp <= boot_stack_top as usize {
+        return Some((boot_stack as usize, boot_stack_top as usize));
+    }
+    None
+}
+
+// 最多打印的调用栈层数
+const MAX_BACKTRACE_DEPTH: usize = 32;
+
+// 沿着 fp 链打印调用栈，内核使用 -Cforce-frame-pointers 编译，每个栈帧中 fp - 8 处是
+// 返回地址，fp - 16 处是调用者的 fp。stack 是栈的范围，超出范围的 fp 不会被访问。
+fn print_backtrace(pc: usize, ra: usize, fp: usize, stack: (usize, usize)) {
+    let (bottom, top) = stack;
+    let valid = |fp: usize| fp % 8 == 0 && fp >= bottom + 16 && fp <= top;
+    let saved_ra = |fp: usize| unsafe { *((fp - 8) as *const usize) };
+    println!("backtrace:");
+    println!("  #0 {:#x}", pc);
+    let mut depth = 1;
+    // 在函数的序言中发生 trap 时返回地址还没有保存到栈上，只在 ra 中
+    if !valid(fp) || saved_ra(fp) != ra {
+        println!("  #1 {:#x}", ra);
+        depth += 1;
+    }
+    let mut fp = fp;
+    while valid(fp) && depth < MAX_BACKTRACE_DEPTH {
+        println!("  #{} {:#x}", depth, saved_ra(fp));
+        depth += 1;
+        let caller_fp = unsafe { *((fp - 16) as *const usize) };
+        // 调用者的栈帧总是在更高的地址
+        if caller_fp <= fp {
+            break;
+        }
+        fp = caller_fp;
+    }
+}
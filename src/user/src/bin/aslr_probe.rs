@@ -0,0 +1,25 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+
+use user_lib::*;
+
+// 由 aslr_test 启动，把栈、堆和 mmap 区域的地址写入 argv[1] 指定的管道
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    assert_eq!(argc, 2);
+    let fd: usize = argv[1].parse().unwrap();
+    let local = 0usize;
+    let addrs = [
+        &local as *const usize as usize,
+        sbrk(0) as usize,
+        mmap(0, 4096, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS) as usize,
+    ];
+    let bytes = unsafe {
+        core::slice::from_raw_parts(addrs.as_ptr() as *const u8, core::mem::size_of_val(&addrs))
+    };
+    assert_eq!(write(fd, bytes), bytes.len() as isize);
+    close(fd);
+    0
+}
@@ -0,0 +1,72 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::{format, vec::Vec};
+use user_lib::*;
+
+// 栈、堆和 mmap 区域的地址
+type Layout = [usize; 3];
+
+// 检查随机化时额外 exec 的次数
+const SAMPLES: usize = 4;
+
+// 在子进程中 exec aslr_probe，通过管道读取它的地址空间布局
+fn probe(no_randomize: bool) -> Layout {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(fds[0]);
+        if no_randomize {
+            assert_eq!(personality(ADDR_NO_RANDOMIZE), 0);
+        }
+        let fd = format!("{}\0", fds[1]);
+        let args = [
+            "aslr_probe\0".as_ptr(),
+            fd.as_ptr(),
+            core::ptr::null::<u8>(),
+        ];
+        exec("aslr_probe\0", &args);
+        exit(-1);
+    }
+    close(fds[1]);
+    let mut layout: Layout = [0; 3];
+    let bytes = unsafe {
+        core::slice::from_raw_parts_mut(
+            layout.as_mut_ptr() as *mut u8,
+            core::mem::size_of::<Layout>(),
+        )
+    };
+    assert_eq!(read(fds[0], bytes), bytes.len() as isize);
+    close(fds[0]);
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, 0);
+    layout
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(personality(PERSONALITY_QUERY), 0);
+
+    // 每次 exec 得到的布局是随机的。两次 exec 的某个区域可能恰好随机到同一个地址，
+    // 所以多采样几次，要求每个区域至少有一次和第一次不同
+    let first = probe(false);
+    let samples: Vec<Layout> = (0..SAMPLES).map(|_| probe(false)).collect();
+    println!("aslr_test: randomized {:#x?} and {:#x?}", first, samples);
+    for (i, addr) in first.iter().enumerate() {
+        assert!(samples.iter().any(|layout| layout[i] != *addr));
+    }
+
+    // 设置 ADDR_NO_RANDOMIZE 之后布局是固定的
+    let first = probe(true);
+    let second = probe(true);
+    println!("aslr_test: fixed {:#x?}", first);
+    assert_eq!(first, second);
+    println!("aslr_test passed!");
+    0
+}
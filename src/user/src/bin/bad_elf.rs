@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+const E_PHOFF: usize = 0x20;
+const E_PHNUM: usize = 0x38;
+
+// 一个只有 elf header 的 riscv64 可执行文件，没有 program headers
+fn elf_header() -> [u8; 64] {
+    let mut header = [0u8; 64];
+    header[..8].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+    header[0x10..0x12].copy_from_slice(&2u16.to_le_bytes());
+    header[0x12..0x14].copy_from_slice(&0xf3u16.to_le_bytes());
+    header[0x14..0x18].copy_from_slice(&1u32.to_le_bytes());
+    header[0x34..0x36].copy_from_slice(&64u16.to_le_bytes());
+    header[0x36..0x38].copy_from_slice(&56u16.to_le_bytes());
+    header[0x3a..0x3c].copy_from_slice(&64u16.to_le_bytes());
+    header
+}
+
+// 把 data 写入文件 path 之后 exec 它，返回 exec 的返回值
+fn exec_file(path: &str, data: &[u8]) -> isize {
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::WRITE_ONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, data), data.len() as isize);
+    close(fd as usize);
+    let args = [path.as_ptr(), core::ptr::null::<u8>()];
+    exec(path, &args)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 文件比 elf header 还短
+    assert_eq!(exec_file("bad_elf_short\0", &elf_header()[..16]), -1);
+
+    // program headers 超出文件末尾
+    let mut header = elf_header();
+    header[E_PHOFF..E_PHOFF + 8].copy_from_slice(&64u64.to_le_bytes());
+    header[E_PHNUM..E_PHNUM + 2].copy_from_slice(&u16::MAX.to_le_bytes());
+    assert_eq!(exec_file("bad_elf_phnum\0", &header), -1);
+
+    // e_phoff 加上 program headers 的长度溢出
+    header[E_PHOFF..E_PHOFF + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert_eq!(exec_file("bad_elf_phoff\0", &header), -1);
+
+    // 不是 elf 文件
+    assert_eq!(exec_file("bad_elf_magic\0", &[0u8; 64]), -1);
+    println!("bad_elf passed!");
+    0
+}
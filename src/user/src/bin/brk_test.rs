@@ -0,0 +1,81 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::{format, string::String, vec::Vec};
+use user_lib::*;
+
+const PAGE_SIZE: usize = 4096;
+
+// 在子进程中访问 addr，访问堆之外的地址应该收到 SIGSEGV
+fn expect_segv(addr: usize) {
+    let pid = fork();
+    if pid == 0 {
+        unsafe {
+            (addr as *mut u8).write_volatile(1);
+        }
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, -SIGSEGV);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 堆的大小可以不按页对齐，新增加的内存被初始化为 0
+    let start = sbrk(0);
+    assert!(start > 0);
+    let start = start as usize;
+    assert_eq!(sbrk((3 * PAGE_SIZE + 10) as isize), start as isize);
+    assert_eq!(sbrk(0), (start + 3 * PAGE_SIZE + 10) as isize);
+    let heap = unsafe { core::slice::from_raw_parts_mut(start as *mut u8, 3 * PAGE_SIZE + 10) };
+    assert!(heap.iter().all(|b| *b == 0));
+    heap.fill(0x5a);
+    println!("brk_test: sbrk grows the heap");
+
+    // 缩小堆之后，被去掉的页不能再访问
+    assert_eq!(brk(start + PAGE_SIZE), 0);
+    assert_eq!(heap[PAGE_SIZE - 1], 0x5a);
+    expect_segv(start + PAGE_SIZE);
+    assert_eq!(sbrk(-(PAGE_SIZE as isize)), (start + PAGE_SIZE) as isize);
+    assert_eq!(sbrk(0), start as isize);
+    expect_segv(start);
+    println!("brk_test: brk shrinks the heap");
+
+    // 不能把堆缩小到起始地址之下，也不能增长到用户栈
+    assert_eq!(brk(start - 1), -1);
+    assert_eq!(sbrk(-1), -1);
+    assert_eq!(sbrk(isize::MAX), -1);
+    assert_eq!(sbrk(0), start as isize);
+
+    // fork 出的子进程继承父进程的堆
+    assert_eq!(sbrk(PAGE_SIZE as isize), start as isize);
+    unsafe { (start as *mut u8).write_volatile(0xa5) };
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(sbrk(0), (start + PAGE_SIZE) as isize);
+        assert_eq!(unsafe { (start as *const u8).read_volatile() }, 0xa5);
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, 0);
+
+    // 静态的堆空间不够用时，分配器通过 sbrk 扩展堆
+    let mut v: Vec<usize> = Vec::new();
+    for i in 0..32768 {
+        v.push(i);
+    }
+    assert!(v.iter().enumerate().all(|(i, x)| i == *x));
+    assert!(sbrk(0) as usize > start + PAGE_SIZE);
+    let s: String = format!("{:?}", &v[..1000]);
+    assert!(s.len() > 4000);
+    drop(v);
+    println!("brk_test: user heap grows through sbrk");
+    println!("brk_test passed!");
+    0
+}
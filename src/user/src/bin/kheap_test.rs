@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::*;
+
+// 大量的管道和很大的文件描述符表需要的内核堆空间超过了内核堆的初始大小
+const PIPES: usize = 4096;
+
+fn open_pipes() -> Vec<[usize; 2]> {
+    let mut pipes = Vec::new();
+    for _ in 0..PIPES {
+        let mut fds = [0usize; 2];
+        assert_eq!(pipe(&mut fds), 0);
+        pipes.push(fds);
+    }
+    pipes
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 内核堆增长之后释放的空间可以被再次使用
+    for round in 0..2 {
+        let pipes = open_pipes();
+        let last = pipes[PIPES - 1];
+        assert_eq!(write(last[1], b"kheap"), 5);
+        let mut buf = [0u8; 5];
+        assert_eq!(read(last[0], &mut buf), 5);
+        assert_eq!(&buf, b"kheap");
+        for fds in pipes {
+            close(fds[0]);
+            close(fds[1]);
+        }
+        println!("kheap_test: round {} opened {} pipes", round, PIPES);
+    }
+    println!("kheap_test passed!");
+    0
+}
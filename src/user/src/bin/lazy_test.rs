@@ -0,0 +1,93 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+const PAGE_SIZE: usize = 4096;
+// 比物理内存还大的映射，只有被访问过的页才会分配页框
+const SPARSE_SIZE: usize = 1 << 30;
+const BSS_SIZE: usize = 16 << 20;
+
+static mut BIG_BSS: [u8; BSS_SIZE] = [0; BSS_SIZE];
+static mut DATA: [u32; 2048] = [0x5a5a_5a5a; 2048];
+
+// 在子进程中访问 addr，访问逻辑段之外的地址应该收到 SIGSEGV
+fn expect_segv(addr: usize) {
+    let pid = fork();
+    if pid == 0 {
+        unsafe {
+            (addr as *mut u8).write_volatile(1);
+        }
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, -SIGSEGV);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // .data 中的数据在第一次访问时才从 elf 中拷贝，.bss 被初始化为 0
+    unsafe {
+        assert!(DATA.iter().all(|x| *x == 0x5a5a_5a5a));
+        for offset in (0..BSS_SIZE).step_by(BSS_SIZE / 4) {
+            assert_eq!(BIG_BSS[offset], 0);
+            BIG_BSS[offset] = 1;
+        }
+        assert_eq!(BIG_BSS[BSS_SIZE - 1], 0);
+    }
+    println!("lazy_test: .data and a large .bss are loaded on demand");
+
+    // 稀疏地访问一段很大的匿名映射
+    let addr = mmap(
+        0,
+        SPARSE_SIZE,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+    );
+    assert!(addr > 0);
+    let addr = addr as usize;
+    for offset in (0..SPARSE_SIZE).step_by(SPARSE_SIZE / 8) {
+        let p = (addr + offset) as *mut usize;
+        unsafe {
+            assert_eq!(p.read_volatile(), 0);
+            p.write_volatile(offset);
+        }
+    }
+    println!("lazy_test: sparse anonymous mapping works");
+
+    // 子进程继承已经加载的页，没有访问过的页仍然为 0
+    let pid = fork();
+    if pid == 0 {
+        unsafe {
+            let p = (addr + SPARSE_SIZE / 8) as *const usize;
+            assert_eq!(p.read_volatile(), SPARSE_SIZE / 8);
+            assert_eq!(((addr + PAGE_SIZE) as *const usize).read_volatile(), 0);
+        }
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, 0);
+
+    // 内核访问还没有加载的用户页时先处理缺页
+    let fds = unsafe { core::slice::from_raw_parts_mut((addr + PAGE_SIZE) as *mut usize, 2) };
+    assert_eq!(pipe(fds), 0);
+    assert_eq!(write(fds[1], b"lazy"), 4);
+    let buf = unsafe { core::slice::from_raw_parts_mut((addr + 2 * PAGE_SIZE) as *mut u8, 4) };
+    assert_eq!(read(fds[0], buf), 4);
+    assert_eq!(buf, b"lazy");
+    close(fds[0]);
+    close(fds[1]);
+    println!("lazy_test: the kernel faults in user buffers");
+
+    // 逻辑段之外的访问仍然是非法的
+    assert_eq!(munmap(addr, SPARSE_SIZE), 0);
+    expect_segv(addr);
+    expect_segv(addr + SPARSE_SIZE / 2);
+    println!("lazy_test passed!");
+    0
+}
@@ -0,0 +1,175 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+const PAGE_SIZE: usize = 4096;
+const FILE_NAME: &str = "mmapfile\0";
+// 文件的最后一页只有一部分在文件中
+const FILE_SIZE: usize = 2 * PAGE_SIZE + 100;
+const MAP_LEN: usize = 3 * PAGE_SIZE;
+
+fn pattern(i: usize) -> u8 {
+    (i % 251) as u8
+}
+
+fn map_bytes(addr: usize, len: usize) -> &'static mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, len) }
+}
+
+fn create_file() {
+    let fd = open(FILE_NAME, OpenFlags::CREATE | OpenFlags::WRITE_ONLY);
+    assert!(fd > 0);
+    let mut chunk = [0u8; 256];
+    let mut written = 0;
+    while written < FILE_SIZE {
+        let len = chunk.len().min(FILE_SIZE - written);
+        for (i, b) in chunk[..len].iter_mut().enumerate() {
+            *b = pattern(written + i);
+        }
+        assert_eq!(write(fd as usize, &chunk[..len]), len as isize);
+        written += len;
+    }
+    close(fd as usize);
+}
+
+// 通过 read 读出整个文件，检查它的长度和内容
+fn check_file(expected: impl Fn(usize) -> u8) {
+    let fd = open(FILE_NAME, OpenFlags::READ_ONLY);
+    assert!(fd > 0);
+    let mut chunk = [0u8; 256];
+    let mut total = 0;
+    loop {
+        let len = read(fd as usize, &mut chunk);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        for (i, b) in chunk[..len as usize].iter().enumerate() {
+            assert_eq!(*b, expected(total + i));
+        }
+        total += len as usize;
+    }
+    close(fd as usize);
+    assert_eq!(total, FILE_SIZE);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    create_file();
+    let fd = open(FILE_NAME, OpenFlags::READ_WRITE);
+    assert!(fd > 0);
+    let fd = fd as usize;
+
+    // 私有映射读到文件的内容，文件末尾之后的部分为 0
+    let addr = mmap_file(0, MAP_LEN, PROT_READ | PROT_WRITE, MAP_PRIVATE, fd, 0);
+    assert!(addr > 0);
+    let buf = map_bytes(addr as usize, MAP_LEN);
+    for (i, b) in buf.iter().enumerate() {
+        assert_eq!(*b, if i < FILE_SIZE { pattern(i) } else { 0 });
+    }
+    // 私有映射的修改不会写回文件
+    buf[0] = 0xff;
+    assert_eq!(munmap(addr as usize, MAP_LEN), 0);
+    check_file(pattern);
+    println!("mmap_file: MAP_PRIVATE works");
+
+    // offset 指定从文件的哪一页开始映射
+    let addr = mmap_file(0, PAGE_SIZE, PROT_READ, MAP_PRIVATE, fd, PAGE_SIZE);
+    assert!(addr > 0);
+    assert_eq!(map_bytes(addr as usize, 1)[0], pattern(PAGE_SIZE));
+    assert_eq!(munmap(addr as usize, PAGE_SIZE), 0);
+
+    // 共享映射的修改在 msync 之后写回文件
+    let addr = mmap_file(0, MAP_LEN, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+    assert!(addr > 0);
+    let addr = addr as usize;
+    let buf = map_bytes(addr, MAP_LEN);
+    buf[1] = 0xaa;
+    buf[2 * PAGE_SIZE + 1] = 0xbb;
+    // 文件末尾之后的修改被丢弃，文件的大小不变
+    buf[FILE_SIZE + 1] = 0xcc;
+    assert_eq!(msync(addr, MAP_LEN, MS_SYNC), 0);
+    check_file(|i| match i {
+        1 => 0xaa,
+        i if i == 2 * PAGE_SIZE + 1 => 0xbb,
+        i => pattern(i),
+    });
+    println!("mmap_file: msync writes back dirty pages");
+
+    // 系统调用也可以直接使用还没有被访问过的映射页作为缓冲区
+    let src = open(FILE_NAME, OpenFlags::READ_ONLY);
+    assert!(src > 0);
+    assert_eq!(
+        read(src as usize, &mut buf[PAGE_SIZE + 2..PAGE_SIZE + 4]),
+        2
+    );
+    close(src as usize);
+
+    // munmap 时写回 msync 之后的修改
+    buf[3] = 0xdd;
+    assert_eq!(munmap(addr, MAP_LEN), 0);
+    check_file(|i| match i {
+        1 => 0xaa,
+        3 => 0xdd,
+        i if i == PAGE_SIZE + 2 => pattern(0),
+        i if i == PAGE_SIZE + 3 => 0xaa,
+        i if i == 2 * PAGE_SIZE + 1 => 0xbb,
+        i => pattern(i),
+    });
+    println!("mmap_file: munmap writes back dirty pages");
+
+    // fork 之后父子进程共享已经映射的页，子进程退出时写回共享映射的修改
+    let addr = mmap_file(0, PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+    assert!(addr > 0);
+    assert_eq!(map_bytes(addr as usize, PAGE_SIZE)[1], 0xaa);
+    let pid = fork();
+    if pid == 0 {
+        map_bytes(addr as usize, PAGE_SIZE)[5] = 0xee;
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, 0);
+    assert_eq!(map_bytes(addr as usize, PAGE_SIZE)[5], 0xee);
+    assert_eq!(munmap(addr as usize, PAGE_SIZE), 0);
+    check_file(|i| match i {
+        1 => 0xaa,
+        3 => 0xdd,
+        5 => 0xee,
+        i if i == PAGE_SIZE + 2 => pattern(0),
+        i if i == PAGE_SIZE + 3 => 0xaa,
+        i if i == 2 * PAGE_SIZE + 1 => 0xbb,
+        i => pattern(i),
+    });
+    println!("mmap_file: exit writes back dirty pages");
+
+    // 不合法的参数
+    let prot = PROT_READ | PROT_WRITE;
+    assert_eq!(mmap_file(0, PAGE_SIZE, prot, MAP_SHARED, fd, 1), -EINVAL);
+    assert_eq!(
+        mmap_file(0, PAGE_SIZE, prot, MAP_SHARED | MAP_PRIVATE, fd, 0),
+        -EINVAL
+    );
+    assert_eq!(mmap_file(0, PAGE_SIZE, prot, MAP_SHARED, 100, 0), -EBADF);
+    assert_eq!(
+        mmap(0, PAGE_SIZE, prot, MAP_SHARED | MAP_ANONYMOUS),
+        -EINVAL
+    );
+    close(fd);
+    // 只读打开的文件不能建立可写的共享映射
+    let fd = open(FILE_NAME, OpenFlags::READ_ONLY) as usize;
+    assert_eq!(mmap_file(0, PAGE_SIZE, prot, MAP_SHARED, fd, 0), -EACCES);
+    assert!(mmap_file(0, PAGE_SIZE, prot, MAP_PRIVATE, fd, 0) > 0);
+    // 也不能通过 mprotect 把只读的共享映射改为可写
+    let addr = mmap_file(0, PAGE_SIZE, PROT_READ, MAP_SHARED, fd, 0);
+    assert!(addr > 0);
+    assert_eq!(mprotect(addr as usize, PAGE_SIZE, prot), -EACCES);
+    assert_eq!(munmap(addr as usize, PAGE_SIZE), 0);
+    close(fd);
+    println!("mmap_file passed!");
+    0
+}
@@ -0,0 +1,93 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+const PAGE_SIZE: usize = 4096;
+
+fn fill(addr: usize, len: usize, value: u8) {
+    let buf = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, len) };
+    buf.fill(value);
+}
+
+fn check(addr: usize, len: usize, value: u8) {
+    let buf = unsafe { core::slice::from_raw_parts(addr as *const u8, len) };
+    assert!(buf.iter().all(|b| *b == value));
+}
+
+// 在子进程中访问 addr，访问已经解除映射的地址应该收到 SIGSEGV
+fn expect_segv(addr: usize) {
+    let pid = fork();
+    if pid == 0 {
+        unsafe {
+            (addr as *mut u8).write_volatile(1);
+        }
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, -SIGSEGV);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let prot = PROT_READ | PROT_WRITE;
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS;
+
+    // 匿名映射的内存被初始化为 0
+    let addr = mmap(0, 3 * PAGE_SIZE, prot, flags);
+    assert!(addr > 0);
+    let addr = addr as usize;
+    check(addr, 3 * PAGE_SIZE, 0);
+    fill(addr, 3 * PAGE_SIZE, 0x5a);
+
+    // 新的映射不会与已有的映射重叠
+    let other = mmap(addr, PAGE_SIZE, prot, flags);
+    assert!(other > 0 && other as usize != addr);
+    assert_eq!(munmap(other as usize, PAGE_SIZE), 0);
+
+    // 解除中间一页的映射，原来的映射被拆分为两部分
+    assert_eq!(munmap(addr + PAGE_SIZE, PAGE_SIZE), 0);
+    check(addr, PAGE_SIZE, 0x5a);
+    check(addr + 2 * PAGE_SIZE, PAGE_SIZE, 0x5a);
+    expect_segv(addr + PAGE_SIZE);
+    println!("mmap_test: partial munmap works");
+
+    // MAP_FIXED 可以重新映射这一页，并且替换掉与之重叠的映射
+    assert_eq!(
+        mmap(addr + PAGE_SIZE, 2 * PAGE_SIZE, prot, flags | MAP_FIXED),
+        (addr + PAGE_SIZE) as isize
+    );
+    check(addr, PAGE_SIZE, 0x5a);
+    check(addr + PAGE_SIZE, 2 * PAGE_SIZE, 0);
+    println!("mmap_test: MAP_FIXED works");
+
+    // MAP_PRIVATE 的映射在 fork 之后互不影响
+    let pid = fork();
+    if pid == 0 {
+        check(addr, PAGE_SIZE, 0x5a);
+        fill(addr, PAGE_SIZE, 0xa5);
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, 0);
+    check(addr, PAGE_SIZE, 0x5a);
+
+    // 不合法的参数
+    assert_eq!(mmap(0, 0, prot, flags), -EINVAL);
+    assert_eq!(mmap(0, PAGE_SIZE, 0, flags), -EINVAL);
+    assert_eq!(mmap(0, PAGE_SIZE, prot, MAP_ANONYMOUS), -EINVAL);
+    assert_eq!(mmap(addr + 1, PAGE_SIZE, prot, flags | MAP_FIXED), -EINVAL);
+    // mmap 区域放不下
+    assert_eq!(mmap(0, 1 << 40, prot, flags), -ENOMEM);
+    assert_eq!(munmap(addr + 1, PAGE_SIZE), -1);
+
+    assert_eq!(munmap(addr, 3 * PAGE_SIZE), 0);
+    expect_segv(addr);
+    println!("mmap_test passed!");
+    0
+}
@@ -0,0 +1,93 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::arch::asm;
+use user_lib::*;
+
+const PAGE_SIZE: usize = 4096;
+
+fn page(addr: usize, i: usize) -> *mut u8 {
+    (addr + i * PAGE_SIZE) as *mut u8
+}
+
+// 在子进程中写入 addr，返回子进程的退出码
+fn try_write(addr: *mut u8) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        unsafe { addr.write_volatile(1) };
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    exit_code
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let rw = PROT_READ | PROT_WRITE;
+    let addr = mmap(0, 4 * PAGE_SIZE, rw, MAP_PRIVATE | MAP_ANONYMOUS);
+    assert!(addr > 0);
+    let addr = addr as usize;
+    for i in 0..4 {
+        unsafe { page(addr, i).write_volatile(i as u8) };
+    }
+
+    // 只修改中间两页的权限，逻辑段被拆分
+    assert_eq!(mprotect(addr + PAGE_SIZE, 2 * PAGE_SIZE, PROT_READ), 0);
+    for i in 0..4 {
+        assert_eq!(unsafe { page(addr, i).read_volatile() }, i as u8);
+    }
+    assert_eq!(try_write(page(addr, 0)), 0);
+    assert_eq!(try_write(page(addr, 1)), -SIGSEGV);
+    assert_eq!(try_write(page(addr, 2)), -SIGSEGV);
+    assert_eq!(try_write(page(addr, 3)), 0);
+    assert_eq!(mprotect(addr, 4 * PAGE_SIZE, rw), 0);
+    unsafe { page(addr, 1).write_volatile(1) };
+    println!("mprotect_test: permissions of part of a mapping change");
+
+    // 范围中有没有被映射的页，或者参数不合法
+    assert_eq!(mprotect(addr, 5 * PAGE_SIZE, PROT_READ), -ENOMEM);
+    assert_eq!(mprotect(addr + 1, PAGE_SIZE, PROT_READ), -EINVAL);
+
+    // W^X：先写入代码，再改为可执行
+    let exec = PROT_READ | PROT_EXEC;
+    assert_eq!(
+        mmap(0, PAGE_SIZE, rw | PROT_EXEC, MAP_PRIVATE | MAP_ANONYMOUS),
+        -EACCES
+    );
+    assert_eq!(mprotect(addr, PAGE_SIZE, rw | PROT_EXEC), -EACCES);
+    let code: [u32; 2] = [
+        0x02a0_0513, // li a0, 42
+        0x0000_8067, // ret
+    ];
+    unsafe {
+        core::ptr::copy_nonoverlapping(code.as_ptr(), addr as *mut u32, code.len());
+    }
+    assert_eq!(mprotect(addr, PAGE_SIZE, exec), 0);
+    let result = unsafe {
+        asm!("fence.i");
+        let f: extern "C" fn() -> usize = core::mem::transmute(addr);
+        f()
+    };
+    assert_eq!(result, 42);
+    assert_eq!(try_write(page(addr, 0)), -SIGSEGV);
+    println!("mprotect_test: generated code runs after mprotect");
+    assert_eq!(munmap(addr, 4 * PAGE_SIZE), 0);
+
+    // 堆被拆分之后仍然可以通过 brk 增长和缩小
+    let old = sbrk(0) as usize;
+    let heap = (old + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+    assert_eq!(brk(heap + 2 * PAGE_SIZE), 0);
+    assert_eq!(mprotect(heap, PAGE_SIZE, PROT_READ), 0);
+    assert_eq!(try_write(page(heap, 0)), -SIGSEGV);
+    assert_eq!(brk(heap + 4 * PAGE_SIZE), 0);
+    unsafe { page(heap, 3).write_volatile(3) };
+    assert_eq!(brk(heap + PAGE_SIZE / 2), 0);
+    assert_eq!(mprotect(heap, PAGE_SIZE, rw), 0);
+    assert_eq!(brk(old), 0);
+    println!("mprotect_test passed!");
+    0
+}
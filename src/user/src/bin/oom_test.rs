@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+const PAGE_SIZE: usize = 4096;
+// 远大于物理内存和交换区之和的匿名映射
+const SIZE: usize = 1 << 30;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 子进程不断写入新的页，物理内存和交换区都被用完之后，
+    // 驻留页最多的子进程被 OOM killer 结束，父进程不受影响
+    let pid = fork();
+    if pid == 0 {
+        let addr = mmap(0, SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS);
+        assert!(addr > 0);
+        for i in 0..SIZE / PAGE_SIZE {
+            unsafe { ((addr as usize + i * PAGE_SIZE) as *mut usize).write_volatile(i) };
+        }
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, -SIGKILL);
+    println!("oom_test: the largest task is killed when memory runs out");
+
+    // 子进程退出后内存被释放，之后依然可以 fork
+    let pid = fork();
+    if pid == 0 {
+        exit(0);
+    }
+    assert!(pid > 0);
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, 0);
+    println!("oom_test passed!");
+    0
+}
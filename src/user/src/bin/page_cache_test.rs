@@ -0,0 +1,105 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr::addr_of_mut;
+use user_lib::*;
+
+const CHILDREN: usize = 4;
+const COPY_NAME: &str = "pagecache\0";
+const CODE: i32 = 37;
+
+// 位于 .data 段，运行同一个程序的进程共享页缓存中的初始内容，写入时各自复制
+static mut VALUE: usize = 42;
+
+fn value() -> usize {
+    unsafe { addr_of_mut!(VALUE).read_volatile() }
+}
+
+fn set_value(value: usize) {
+    unsafe { addr_of_mut!(VALUE).write_volatile(value) }
+}
+
+// 在子进程中 exec path，返回子进程的退出码
+fn run(path: &str, arg: &str) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        let args = [path.as_ptr(), arg.as_ptr(), core::ptr::null::<u8>()];
+        exec(path, &args);
+        exit(-1);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    exit_code
+}
+
+// 把文件 src 的内容复制到 dst，dst 已经存在时先被截断
+fn copy_file(src: &str, dst: &str) {
+    let src = open(src, OpenFlags::READ_ONLY);
+    let dst = open(dst, OpenFlags::CREATE | OpenFlags::WRITE_ONLY);
+    assert!(src > 0 && dst > 0);
+    let mut buf = [0u8; 512];
+    loop {
+        let len = read(src as usize, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        assert_eq!(write(dst as usize, &buf[..len as usize]), len);
+    }
+    close(src as usize);
+    close(dst as usize);
+}
+
+// 被 exec 的子进程：.data 中是程序的初始内容，写入之后只有自己能看到
+fn child() -> i32 {
+    assert_eq!(value(), 42);
+    set_value(getpid() as usize);
+    for _ in 0..10 {
+        yield_();
+    }
+    assert_eq!(value(), getpid() as usize);
+    0
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc == 2 && argv[1] == "child" {
+        return child();
+    }
+    if argc == 2 && argv[1] == "code" {
+        return CODE;
+    }
+
+    // 多个进程同时运行同一个程序，父进程修改过的 .data 不影响它们
+    set_value(7);
+    let mut pids = [0isize; CHILDREN];
+    for pid in pids.iter_mut() {
+        *pid = fork();
+        if *pid == 0 {
+            let args = [
+                "page_cache_test\0".as_ptr(),
+                "child\0".as_ptr(),
+                core::ptr::null::<u8>(),
+            ];
+            exec("page_cache_test\0", &args);
+            exit(-1);
+        }
+    }
+    for pid in pids {
+        let mut exit_code = 0;
+        waitpid(pid as usize, &mut exit_code);
+        assert_eq!(exit_code, 0);
+    }
+    assert_eq!(value(), 7);
+    println!("page_cache_test: copy-on-write data segments are private");
+
+    // 可执行文件被改写之后，再次 exec 运行的是新的内容
+    copy_file("hello_world\0", COPY_NAME);
+    assert_eq!(run(COPY_NAME, "\0"), 0);
+    copy_file("page_cache_test\0", COPY_NAME);
+    assert_eq!(run(COPY_NAME, "code\0"), CODE);
+    println!("page_cache_test passed!");
+    0
+}
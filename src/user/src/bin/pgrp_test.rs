@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = getpid() as usize;
+    // 子进程继承 parent 的进程组
+    let parent_pgid = getpgid(0) as usize;
+    assert_eq!(setpgid(0, 0), 0);
+    assert_eq!(getpgid(0) as usize, pid);
+    assert_eq!(getpgid(pid), pid as isize);
+
+    let child = fork();
+    if child == 0 {
+        assert_eq!(getpgid(0) as usize, pid);
+        loop {
+            yield_();
+        }
+    }
+    let child = child as usize;
+    assert_eq!(getpgid(child) as usize, pid);
+    // 不能加入不存在的进程组
+    assert_eq!(setpgid(child, 12345), -1);
+    assert_eq!(setpgid(child, 0), 0);
+    assert_eq!(getpgid(child) as usize, child);
+
+    // 设置和查询控制台的前台进程组
+    let old_foreground = tcgetpgrp(0);
+    assert_eq!(tcsetpgrp(0, child), 0);
+    assert_eq!(tcgetpgrp(0) as usize, child);
+    // 不能设置为不存在的进程组
+    assert_eq!(tcsetpgrp(0, 12345), -ESRCH);
+    assert_eq!(tcgetpgrp(0) as usize, child);
+    if old_foreground > 0 {
+        assert_eq!(tcsetpgrp(0, old_foreground as usize), 0);
+    }
+
+    kill(child, SIGKILL);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(child, &mut exit_code), child as isize);
+    assert_eq!(exit_code, -SIGKILL);
+    // 恢复原来的进程组，避免影响 shell 的前台进程组
+    assert_eq!(setpgid(0, parent_pgid), 0);
+    println!("pgrp_test passed!");
+    0
+}
@@ -0,0 +1,85 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+fn add(a: usize, b: usize) -> usize {
+    a + b
+}
+
+fn mul(a: usize, b: usize) -> usize {
+    a * b
+}
+
+// 以 static-pie 方式链接时，这些静态数据中的指针需要内核完成 R_RISCV_RELATIVE 重定位
+static NAMES: [&str; 2] = ["add", "mul"];
+static OPS: [fn(usize, usize) -> usize; 2] = [add, mul];
+static ANSWER: usize = 42;
+static ANSWER_PTR: &usize = &ANSWER;
+
+// 程序头表中的一项
+#[repr(C)]
+struct ProgramHeader {
+    p_type: u32,
+    p_flags: u32,
+    p_offset: u64,
+    p_vaddr: u64,
+    p_paddr: u64,
+    p_filesz: u64,
+    p_memsz: u64,
+    p_align: u64,
+}
+
+const PT_LOAD: u32 = 1;
+
+extern "C" {
+    fn _start();
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let base = getauxval(AT_BASE).unwrap();
+    let entry = getauxval(AT_ENTRY).unwrap();
+    let phdr = getauxval(AT_PHDR).unwrap();
+    let phnum = getauxval(AT_PHNUM).unwrap();
+    assert_eq!(getauxval(AT_PAGESZ), Some(4096));
+    assert_eq!(
+        getauxval(AT_PHENT),
+        Some(core::mem::size_of::<ProgramHeader>())
+    );
+    println!(
+        "pie_test: base {:#x}, entry {:#x}, phdr {:#x}",
+        base, entry, phdr
+    );
+
+    // pie_test 总是链接为 static-pie，内核为它选择了非 0 的加载基址
+    assert_ne!(base, 0);
+
+    // 入口地址就是加载之后 _start 的地址
+    assert_eq!(entry, _start as usize);
+
+    // 程序头表被映射到了 AT_PHDR，其中有包含 main 的 LOAD 段
+    let headers = unsafe { core::slice::from_raw_parts(phdr as *const ProgramHeader, phnum) };
+    let main_addr = main as usize;
+    assert!(headers.iter().any(|ph| {
+        let start = base + ph.p_vaddr as usize;
+        ph.p_type == PT_LOAD && main_addr >= start && main_addr < start + ph.p_memsz as usize
+    }));
+
+    // 静态数据中的字符串和函数指针指向加载后的地址
+    assert_eq!(NAMES[0], "add");
+    assert_eq!(NAMES[1], "mul");
+    assert_eq!(OPS[0](6, 7), 13);
+    assert_eq!(OPS[1](6, 7), 42);
+    // 静态数据中的指针被重定位到了 ANSWER 加载后的地址，而不是它的链接地址。
+    // 用 volatile 读取，避免编译器直接使用编译时已知的值
+    let answer_ptr = unsafe { core::ptr::read_volatile(&ANSWER_PTR) };
+    assert!(core::ptr::eq(answer_ptr, &ANSWER));
+    assert!(answer_ptr as *const usize as usize >= base);
+    assert_eq!(*answer_ptr, 42);
+    println!("pie_test passed!");
+    0
+}
@@ -0,0 +1,98 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+const KEY: usize = 0x5348;
+const SIZE: usize = 64 * 1024;
+
+fn buffer(addr: usize) -> &'static mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, SIZE) }
+}
+
+fn wait_child(pid: isize) -> i32 {
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    exit_code
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let id = shmget(KEY, SIZE, IPC_CREAT | IPC_EXCL);
+    assert!(id > 0);
+    let id = id as usize;
+    assert_eq!(shmget(KEY, SIZE, IPC_CREAT | IPC_EXCL), -EEXIST);
+    assert_eq!(shmget(KEY, SIZE, 0), id as isize);
+    assert_eq!(shmget(KEY, 2 * SIZE, 0), -EINVAL);
+    assert_eq!(shmget(KEY + 1, SIZE, 0), -ENOENT);
+    // 大小按页向上取整时溢出，或者超过了物理内存
+    assert_eq!(shmget(IPC_PRIVATE, usize::MAX, IPC_CREAT), -EINVAL);
+    assert_eq!(shmget(IPC_PRIVATE, 1 << 40, IPC_CREAT), -EINVAL);
+
+    let addr = shmat(id, 0, 0);
+    assert!(addr > 0);
+    let buf = buffer(addr as usize);
+    assert!(buf.iter().all(|b| *b == 0));
+
+    // fork 之后子进程与父进程映射的是同样的页框
+    let pid = fork();
+    if pid == 0 {
+        for (i, b) in buffer(addr as usize).iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        exit(0);
+    }
+    assert_eq!(wait_child(pid), 0);
+    assert!(buf.iter().enumerate().all(|(i, b)| *b == i as u8));
+    println!("shm_test: the segment is shared across fork");
+
+    // 子进程通过 key 找到同一个段，映射到另一个地址
+    let pid = fork();
+    if pid == 0 {
+        let id = shmget(KEY, 0, 0);
+        let other = shmat(id as usize, 0, 0);
+        assert!(other > 0 && other != addr);
+        let other = buffer(other as usize);
+        assert!(other.iter().enumerate().all(|(i, b)| *b == i as u8));
+        other.fill(0xa5);
+        assert_eq!(shmdt(other.as_ptr() as usize), 0);
+        exit(0);
+    }
+    assert_eq!(wait_child(pid), 0);
+    assert!(buf.iter().all(|b| *b == 0xa5));
+    println!("shm_test: the segment is found by key");
+
+    // 只读映射不能写入
+    let pid = fork();
+    if pid == 0 {
+        let readonly = shmat(id, 0, SHM_RDONLY);
+        assert!(readonly > 0);
+        assert_eq!(unsafe { (readonly as *const u8).read_volatile() }, 0xa5);
+        unsafe { (readonly as *mut u8).write_volatile(0) };
+        exit(0);
+    }
+    assert_eq!(wait_child(pid), -SIGSEGV);
+
+    // 只读映射也不能通过 mprotect 改为可写
+    let readonly = shmat(id, 0, SHM_RDONLY);
+    assert!(readonly > 0);
+    let readonly = readonly as usize;
+    assert_eq!(mprotect(readonly, SIZE, PROT_READ | PROT_WRITE), -EACCES);
+    assert_eq!(mprotect(readonly, SIZE, PROT_READ), 0);
+    assert_eq!(shmdt(readonly), 0);
+    println!("shm_test: read-only attaches stay read-only");
+
+    // 段被删除之后已有的映射仍然可以使用
+    assert_eq!(shmctl(id, IPC_RMID), 0);
+    assert_eq!(shmget(KEY, SIZE, 0), -ENOENT);
+    assert_eq!(shmat(id, 0, 0), -EINVAL);
+    buf[0] = 1;
+    assert_eq!(buf[0], 1);
+    assert_eq!(shmdt(addr as usize), 0);
+    assert_eq!(shmdt(addr as usize), -EINVAL);
+    println!("shm_test passed!");
+    0
+}
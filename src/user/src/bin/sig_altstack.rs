@@ -0,0 +1,63 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+static mut ALT_STACK: [u8; SIGSTKSZ] = [0; SIGSTKSZ];
+
+fn segv_handler(signum: usize, info: &SignalInfo, _frame: usize) {
+    assert_eq!(signum, SIGSEGV as usize);
+    let mut old = SignalStack::default();
+    sigaltstack(core::ptr::null(), &mut old);
+    assert!(
+        old.flags & SS_ONSTACK != 0,
+        "Handler is not on the alternate stack!"
+    );
+    println!("sig_altstack: caught stack overflow at {:#x}", info.addr);
+    // 用户栈已经溢出，信号处理函数返回后会再次触发异常，所以直接退出
+    exit(0);
+}
+
+// 每一层都在栈上放一个数组，并且在递归返回后再使用它，避免被优化为循环
+fn recurse(depth: usize) -> usize {
+    let mut buf = [0u8; 256];
+    unsafe {
+        core::ptr::write_volatile(&mut buf[0], depth as u8);
+    }
+    if depth == usize::MAX {
+        return 0;
+    }
+    recurse(depth + 1) + unsafe { core::ptr::read_volatile(&buf[0]) } as usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        let ss = SignalStack {
+            sp: unsafe { ALT_STACK.as_ptr() as usize },
+            flags: 0,
+            size: SIGSTKSZ,
+        };
+        if sigaltstack(&ss, core::ptr::null_mut()) < 0 {
+            panic!("Sigaltstack failed!");
+        }
+        let mut new = SignalAction::default();
+        let old = SignalAction::default();
+        new.handler = segv_handler as usize;
+        new.flags = SignalActionFlags::SA_SIGINFO | SignalActionFlags::SA_ONSTACK;
+        if sigaction(SIGSEGV, &new, &old) < 0 {
+            panic!("Sigaction failed!");
+        }
+        recurse(0);
+        exit(-1);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, 0);
+    println!("sig_altstack passed!");
+    0
+}
@@ -0,0 +1,112 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr::{addr_of, addr_of_mut};
+use user_lib::*;
+
+static mut HANDLED: usize = 0;
+// 不为 0 时，父进程的信号处理函数用 SIGUSR2 通知这个子进程信号已经被处理
+static mut CHILD: usize = 0;
+// 子进程是否收到了父进程的 SIGUSR2
+static mut ACKED: bool = false;
+
+fn usr1_handler(_signum: usize) {
+    unsafe {
+        HANDLED += 1;
+        if CHILD != 0 {
+            kill(CHILD, SIGUSR2);
+        }
+    }
+}
+
+fn usr2_handler(_signum: usize) {
+    unsafe {
+        ACKED = true;
+    }
+}
+
+fn handled() -> usize {
+    unsafe { addr_of!(HANDLED).read_volatile() }
+}
+
+fn acked() -> bool {
+    unsafe { addr_of!(ACKED).read_volatile() }
+}
+
+fn install_handler(signum: i32, handler: fn(usize), flags: SignalActionFlags) {
+    let mut new = SignalAction::default();
+    let old = SignalAction::default();
+    new.handler = handler as usize;
+    new.flags = flags;
+    if sigaction(signum, &new, &old) < 0 {
+        panic!("Sigaction failed!");
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let parent = getpid() as usize;
+    // data 由子进程写给父进程，ready 由父进程通知子进程它将要再次 read
+    let mut data = [0usize; 2];
+    let mut ready = [0usize; 2];
+    assert_eq!(pipe(&mut data), 0);
+    assert_eq!(pipe(&mut ready), 0);
+    install_handler(SIGUSR1, usr1_handler, SignalActionFlags::empty());
+    install_handler(SIGUSR2, usr2_handler, SignalActionFlags::empty());
+
+    let pid = fork();
+    if pid == 0 {
+        close(data[0]);
+        close(ready[1]);
+        // 第一个信号可能在父进程阻塞在 read 之前到达，所以一直重发，
+        // 直到父进程的 read 返回 EINTR 之后用 SIGUSR2 确认
+        while !acked() {
+            kill(parent, SIGUSR1);
+            sleep(20);
+        }
+        // 父进程写入 ready 之后才会阻塞在 read 上，单核上子进程此时才能运行
+        let mut buf = [0u8; 1];
+        assert_eq!(read(ready[0], &mut buf), 1);
+        unsafe {
+            *addr_of_mut!(ACKED) = false;
+        }
+        kill(parent, SIGUSR1);
+        // 等信号处理函数执行完之后再写入数据，这样 read 一定是被打断之后重新执行的
+        while !acked() {
+            yield_();
+        }
+        write(data[1], b"x");
+        close(data[1]);
+        close(ready[0]);
+        exit(0);
+    }
+    close(ready[0]);
+
+    let mut buf = [0u8; 1];
+    // 没有 SA_RESTART：阻塞的 read 被信号打断并返回 EINTR
+    assert_eq!(read(data[0], &mut buf), -EINTR);
+    assert!(handled() >= 1);
+    kill(pid as usize, SIGUSR2);
+    println!("sig_eintr: read was interrupted");
+
+    // 设置了 SA_RESTART：信号处理函数返回后 read 被重新执行
+    install_handler(SIGUSR1, usr1_handler, SignalActionFlags::SA_RESTART);
+    unsafe {
+        *addr_of_mut!(CHILD) = pid as usize;
+    }
+    let before = handled();
+    assert_eq!(write(ready[1], b"r"), 1);
+    assert_eq!(read(data[0], &mut buf), 1);
+    assert_eq!(buf[0], b'x');
+    assert!(handled() > before);
+    println!("sig_eintr: read was restarted");
+
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, 0);
+    println!("sig_eintr passed!");
+    0
+}
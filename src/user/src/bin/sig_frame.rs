@@ -0,0 +1,98 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::arch::asm;
+use core::ptr::addr_of_mut;
+use user_lib::*;
+
+static mut USR1_SENDER: isize = -1;
+static mut USR2_HANDLED: bool = false;
+static mut VTALRM_HANDLED: u32 = 0;
+
+fn vtalrm_handler(_signum: usize) {
+    unsafe {
+        VTALRM_HANDLED = 1;
+    }
+}
+
+fn usr2_handler(_signum: usize) {
+    unsafe {
+        USR2_HANDLED = true;
+    }
+}
+
+fn usr1_handler(signum: usize, info: &SignalInfo, _frame: usize) {
+    assert_eq!(signum, SIGUSR1 as usize);
+    assert_eq!(info.signo, SIGUSR1);
+    assert_eq!(info.code, SI_USER);
+    unsafe {
+        USR1_SENDER = info.pid as isize;
+    }
+    // 在信号处理函数中触发另一个信号，内核会在当前信号帧之下压入新的信号帧
+    if kill(getpid() as usize, SIGUSR2) < 0 {
+        panic!("Kill failed!");
+    }
+    unsafe {
+        assert!(USR2_HANDLED, "Nested signal was not delivered!");
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut usr1 = SignalAction::default();
+    usr1.handler = usr1_handler as usize;
+    usr1.flags = SignalActionFlags::SA_SIGINFO;
+    let mut usr2 = SignalAction::default();
+    usr2.handler = usr2_handler as usize;
+    let old = SignalAction::default();
+    if sigaction(SIGUSR1, &usr1, &old) < 0 || sigaction(SIGUSR2, &usr2, &old) < 0 {
+        panic!("Sigaction failed!");
+    }
+
+    // 信号处理函数返回后，被打断的上下文（包括局部变量）应该被完整恢复
+    let magic = 0x5a5a_a5a5usize;
+    if kill(getpid() as usize, SIGUSR1) < 0 {
+        panic!("Kill failed!");
+    }
+    assert_eq!(magic, 0x5a5a_a5a5usize);
+    unsafe {
+        assert_eq!(USR1_SENDER, getpid());
+    }
+
+    // 被打断时 a0 恰好等于 -ERESTARTSYS，sigreturn 之后也要原样恢复，
+    // 不能被当作需要重新执行的系统调用
+    let mut vtalrm = SignalAction::default();
+    vtalrm.handler = vtalrm_handler as usize;
+    if sigaction(SIGVTALRM, &vtalrm, &old) < 0 {
+        panic!("Sigaction failed!");
+    }
+    let timer = ITimerVal {
+        interval: TimeVal {
+            sec: 0,
+            usec: 10_000,
+        },
+        value: TimeVal {
+            sec: 0,
+            usec: 10_000,
+        },
+    };
+    assert_eq!(setitimer(ITIMER_VIRTUAL, &timer, None), 0);
+    let a0: isize;
+    unsafe {
+        asm!(
+            "1:",
+            "lw {t}, 0({flag})",
+            "beqz {t}, 1b",
+            flag = in(reg) addr_of_mut!(VTALRM_HANDLED),
+            t = out(reg) _,
+            inout("a0") -512isize => a0,
+        );
+    }
+    assert_eq!(setitimer(ITIMER_VIRTUAL, &ITimerVal::default(), None), 0);
+    assert_eq!(a0, -512);
+    println!("sig_frame passed!");
+    0
+}
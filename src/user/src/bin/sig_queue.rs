@@ -0,0 +1,63 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+const MAX_RECORDS: usize = 8;
+
+static mut RECORDS: [(i32, usize); MAX_RECORDS] = [(0, 0); MAX_RECORDS];
+static mut RECORD_COUNT: usize = 0;
+
+fn record_handler(_signum: usize, info: &SignalInfo, _frame: usize) {
+    unsafe {
+        assert!(RECORD_COUNT < MAX_RECORDS);
+        RECORDS[RECORD_COUNT] = (info.signo, info.value);
+        RECORD_COUNT += 1;
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut action = SignalAction::default();
+    action.handler = record_handler as usize;
+    action.flags = SignalActionFlags::SA_SIGINFO;
+    let old = SignalAction::default();
+    for signum in [SIGUSR1, SIGRTMIN, SIGRTMIN + 1] {
+        if sigaction(signum, &action, &old) < 0 {
+            panic!("Sigaction failed!");
+        }
+    }
+
+    // 先屏蔽信号，让它们全部处于未决状态
+    let blocked = SignalFlags::SIGUSR1 | SignalFlags::SIGRT;
+    sigprocmask(SIG_BLOCK, Some(blocked), None);
+    let pid = getpid() as usize;
+    for value in 1..=3 {
+        assert_eq!(sigqueue(pid, SIGRTMIN, value), 0);
+    }
+    assert_eq!(sigqueue(pid, SIGRTMIN + 1, 10), 0);
+    // 标准信号不会排队，重复发送只会递送一次
+    assert_eq!(sigqueue(pid, SIGUSR1, 100), 0);
+    assert_eq!(sigqueue(pid, SIGUSR1, 200), 0);
+    sigprocmask(SIG_UNBLOCK, Some(blocked), None);
+
+    // 信号按编号从小到大递送，同一个实时信号按照发送顺序递送
+    let expected = [
+        (SIGUSR1, 100),
+        (SIGRTMIN, 1),
+        (SIGRTMIN, 2),
+        (SIGRTMIN, 3),
+        (SIGRTMIN + 1, 10),
+    ];
+    unsafe {
+        assert_eq!(RECORD_COUNT, expected.len());
+        for (i, record) in expected.iter().enumerate() {
+            assert_eq!(RECORDS[i], *record);
+        }
+    }
+    println!("sig_queue passed!");
+    0
+}
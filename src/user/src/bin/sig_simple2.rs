@@ -4,7 +4,7 @@
 #[macro_use]
 extern crate user_lib;
 
-use user_lib::{exit, fork, kill, sigaction, sigreturn, sleep, waitpid, SignalAction};
+use user_lib::{exit, fork, kill, sigaction, sigreturn, sleep, waitpid, SignalAction, SIGUSR1};
 
 fn func() {
     println!("user_sig_test success");
@@ -20,7 +20,7 @@ pub fn main() -> i32 {
         new.handler = func as usize;
 
         println!("signal_simple2: child sigaction");
-        if sigaction(10, &new, &old) < 0 {
+        if sigaction(SIGUSR1, &new, &old) < 0 {
             panic!("Sigaction failed!");
         }
         sleep(1000);
@@ -29,7 +29,7 @@ pub fn main() -> i32 {
     } else if pid > 0 {
         println!("signal_simple2: parent kill child");
         sleep(500);
-        if kill(pid as usize, 1 << 10) < 0 {
+        if kill(pid as usize, SIGUSR1) < 0 {
             println!("Kill failed!");
             exit(1);
         }
@@ -0,0 +1,79 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr::addr_of;
+use user_lib::*;
+
+static mut CHLD_CODE: i32 = 0;
+static mut CHLD_PID: usize = 0;
+
+fn chld_handler(_signum: usize, info: &SignalInfo, _frame: usize) {
+    unsafe {
+        CHLD_CODE = info.code;
+        CHLD_PID = info.pid;
+    }
+}
+
+fn last_chld() -> (i32, usize) {
+    unsafe {
+        (
+            addr_of!(CHLD_CODE).read_volatile(),
+            addr_of!(CHLD_PID).read_volatile(),
+        )
+    }
+}
+
+fn spawn_spinner() -> usize {
+    let pid = fork();
+    if pid == 0 {
+        loop {
+            yield_();
+        }
+    }
+    pid as usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut action = SignalAction::default();
+    action.handler = chld_handler as usize;
+    action.flags = SignalActionFlags::SA_SIGINFO;
+    let old = SignalAction::default();
+    if sigaction(SIGCHLD, &action, &old) < 0 {
+        panic!("Sigaction failed!");
+    }
+
+    // 子进程被暂停后 parent 可以通过 waitpid 和 SIGCHLD 得知
+    let pid = spawn_spinner();
+    let mut status = 0;
+    assert_eq!(kill(pid, SIGSTOP), 0);
+    assert_eq!(waitpid_options(pid, &mut status, WUNTRACED), pid as isize);
+    assert!(wifstopped(status));
+    assert_eq!(wstopsig(status), SIGSTOP);
+    assert_eq!(last_chld(), (CLD_STOPPED, pid));
+    println!("sig_stop: child stopped");
+
+    assert_eq!(kill(pid, SIGCONT), 0);
+    assert_eq!(waitpid_options(pid, &mut status, WCONTINUED), pid as isize);
+    assert!(wifcontinued(status));
+    assert_eq!(last_chld(), (CLD_CONTINUED, pid));
+    // 子进程恢复后可以继续处理信号
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    assert_eq!(waitpid(pid, &mut status), pid as isize);
+    assert_eq!(status, -SIGUSR1);
+    println!("sig_stop: child continued");
+
+    // SIGKILL 可以结束被暂停的子进程
+    let pid = spawn_spinner();
+    assert_eq!(kill(pid, SIGTSTP), 0);
+    assert_eq!(waitpid_options(pid, &mut status, WUNTRACED), pid as isize);
+    assert_eq!(wstopsig(status), SIGTSTP);
+    assert_eq!(kill(pid, SIGKILL), 0);
+    assert_eq!(waitpid(pid, &mut status), pid as isize);
+    assert_eq!(status, -SIGKILL);
+    println!("sig_stop passed!");
+    0
+}
@@ -0,0 +1,93 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr::addr_of;
+use user_lib::*;
+
+static mut HANDLED: usize = 0;
+
+fn usr1_handler(_signum: usize) {
+    unsafe {
+        HANDLED += 1;
+    }
+}
+
+fn handled() -> usize {
+    unsafe { addr_of!(HANDLED).read_volatile() }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut action = SignalAction::default();
+    action.handler = usr1_handler as usize;
+    let old = SignalAction::default();
+    if sigaction(SIGUSR1, &action, &old) < 0 {
+        panic!("Sigaction failed!");
+    }
+
+    // SIG_BLOCK 屏蔽 SIGUSR1 后它会保持未决，SIG_UNBLOCK 后立即被递送
+    let mut mask = SignalFlags::empty();
+    assert_eq!(
+        sigprocmask(SIG_BLOCK, Some(SignalFlags::SIGUSR1), Some(&mut mask)),
+        0
+    );
+    assert!(mask.is_empty());
+    kill(getpid() as usize, SIGUSR1);
+    let mut pending = SignalFlags::empty();
+    assert_eq!(sigpending(&mut pending), 0);
+    assert_eq!(pending, SignalFlags::SIGUSR1);
+    assert_eq!(handled(), 0);
+    assert_eq!(
+        sigprocmask(SIG_UNBLOCK, Some(SignalFlags::SIGUSR1), None),
+        0
+    );
+    assert_eq!(handled(), 1);
+    assert_eq!(sigpending(&mut pending), 0);
+    assert!(pending.is_empty());
+    println!("sig_suspend: block and unblock works");
+
+    // 屏蔽 SIGUSR1 之后再等待，子进程在此期间发送的信号不会被错过
+    sigprocmask(SIG_SETMASK, Some(SignalFlags::SIGUSR1), None);
+    let parent = getpid() as usize;
+    let pid = fork();
+    if pid == 0 {
+        sleep(200);
+        kill(parent, SIGUSR1);
+        exit(0);
+    }
+    assert_eq!(sigsuspend(SignalFlags::empty()), -EINTR);
+    assert_eq!(handled(), 2);
+    // sigsuspend 返回后恢复原来的掩码
+    assert_eq!(sigprocmask(SIG_BLOCK, None, Some(&mut mask)), 0);
+    assert_eq!(mask, SignalFlags::SIGUSR1);
+    println!("sig_suspend: sigsuspend works");
+
+    // 被忽略的信号和默认动作为忽略的信号不会使 sigsuspend 返回
+    let mut ignore = SignalAction::default();
+    ignore.handler = SIG_IGN;
+    assert_eq!(sigaction(SIGUSR2, &ignore, &old), 0);
+    let child = fork();
+    if child == 0 {
+        sleep(100);
+        kill(parent, SIGUSR2);
+        kill(parent, SIGCHLD);
+        sleep(100);
+        kill(parent, SIGUSR1);
+        exit(0);
+    }
+    assert_eq!(sigsuspend(SignalFlags::empty()), -EINTR);
+    assert_eq!(handled(), 3);
+    let mut exit_code = 0;
+    waitpid(child as usize, &mut exit_code);
+    println!("sig_suspend: ignored signals do not wake sigsuspend");
+
+    // 不合法的 how
+    assert_eq!(sigprocmask(3, Some(SignalFlags::SIGUSR2), None), -1);
+
+    waitpid(pid as usize, &mut exit_code);
+    println!("sig_suspend passed!");
+    0
+}
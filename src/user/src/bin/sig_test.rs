@@ -25,8 +25,17 @@ fn user_sig_test_failsignum() {
     let mut new = SignalAction::default();
     let old = SignalAction::default();
     new.handler = func as usize;
-    if sigaction(50, &new, &old) >= 0 {
-        panic!("Wrong sigaction but success!");
+    for signum in [0, SIGRTMAX + 1] {
+        if sigaction(signum, &new, &old) >= 0 {
+            panic!("Wrong sigaction but success!");
+        }
+    }
+    // 实时信号 SIGRTMIN..=SIGRTMAX 都可以设置处理函数
+    for signum in SIGRTMIN..=SIGRTMAX {
+        if sigaction(signum, &new, &old) < 0 {
+            panic!("Sigaction of real-time signal {} failed!", signum);
+        }
+        sigaction(signum, &SignalAction::default(), &old);
     }
 }
 
@@ -84,22 +93,37 @@ fn user_sig_test_restore() {
 }
 
 fn kernel_sig_test_ignore() {
-    sigprocmask(SignalFlags::SIGSTOP.bits() as u32);
-    if kill(getpid() as usize, SignalFlags::SIGSTOP.bits() as i32) < 0 {
+    // 被屏蔽的信号保持未决，不会执行默认动作结束进程
+    sigprocmask(SIG_BLOCK, Some(SignalFlags::SIGUSR1), None);
+    if kill(getpid() as usize, SIGUSR1) < 0 {
         println!("kill faild\n");
         exit(-1);
     }
+    let mut pending = SignalFlags::empty();
+    sigpending(&mut pending);
+    if pending != SignalFlags::SIGUSR1 {
+        println!("SIGUSR1 should be pending!");
+        exit(-1);
+    }
+    // SIGSTOP 不能被屏蔽
+    let mut mask = SignalFlags::empty();
+    sigprocmask(SIG_BLOCK, Some(SignalFlags::SIGSTOP), Some(&mut mask));
+    sigprocmask(SIG_BLOCK, None, Some(&mut mask));
+    if mask.contains(SignalFlags::SIGSTOP) {
+        println!("SIGSTOP should not be maskable!");
+        exit(-1);
+    }
 }
 
 fn kernel_sig_test_stop_cont() {
     let pid = fork();
     if pid == 0 {
-        kill(getpid() as usize, SignalFlags::SIGSTOP.bits() as i32);
+        kill(getpid() as usize, SIGSTOP);
         sleep(1000);
         exit(-1);
     } else {
         sleep(5000);
-        kill(pid as usize, SignalFlags::SIGCONT.bits() as i32);
+        kill(pid as usize, SIGCONT);
         let mut exit_code = 0;
         wait(&mut exit_code);
     }
@@ -110,15 +134,15 @@ fn kernel_sig_test_failignorekill() {
     let old = SignalAction::default();
     new.handler = func as usize;
 
-    if sigaction(9, &new, &old) >= 0 {
+    if sigaction(SIGKILL, &new, &old) >= 0 {
         panic!("Should not set sigaction to kill!");
     }
 
-    if sigaction(9, &new, 0 as *const SignalAction) >= 0 {
+    if sigaction(SIGKILL, &new, 0 as *const SignalAction) >= 0 {
         panic!("Should not set sigaction to kill!");
     }
 
-    if sigaction(9, 0 as *const SignalAction, &old) >= 0 {
+    if sigaction(SIGKILL, 0 as *const SignalAction, &old) >= 0 {
         panic!("Should not set sigaction to kill!");
     }
 }
@@ -137,7 +161,7 @@ fn final_sig_test() {
         if sigaction(SIGUSR1, &new, &old) < 0 {
             panic!("Sigaction failed!");
         }
-        if sigaction(14, &new2, &old2) < 0 {
+        if sigaction(SIGALRM, &new2, &old2) < 0 {
             panic!("Sigaction failed!");
         }
         if kill(getpid() as usize, SIGUSR1) < 0 {
@@ -146,12 +170,12 @@ fn final_sig_test() {
         }
     } else {
         sleep(1000);
-        if kill(pid as usize, 1 << 14) < 0 {
+        if kill(pid as usize, SIGALRM) < 0 {
             println!("Kill failed!");
             exit(-1);
         }
         sleep(1000);
-        kill(pid as usize, SignalFlags::SIGKILL.bits() as i32);
+        kill(pid as usize, SIGKILL);
     }
 }
 
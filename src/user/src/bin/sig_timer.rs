@@ -0,0 +1,115 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr::addr_of;
+use user_lib::*;
+
+static mut ALRM_COUNT: usize = 0;
+static mut VTALRM_COUNT: usize = 0;
+static mut PROF_COUNT: usize = 0;
+
+fn timer_handler(signum: usize) {
+    unsafe {
+        match signum as i32 {
+            SIGALRM => ALRM_COUNT += 1,
+            SIGVTALRM => VTALRM_COUNT += 1,
+            SIGPROF => PROF_COUNT += 1,
+            _ => panic!("Unexpected signal {}", signum),
+        }
+    }
+}
+
+// 等待 count 的值达到 target，yield 为 true 时让出 CPU，否则一直占用 CPU
+fn wait_for(count: *const usize, target: usize, yield_cpu: bool) {
+    let start = get_time();
+    while unsafe { count.read_volatile() } < target {
+        if get_time() - start > 3000 {
+            panic!("Timer did not fire!");
+        }
+        if yield_cpu {
+            yield_();
+        }
+    }
+}
+
+fn ms(ms: usize) -> TimeVal {
+    TimeVal {
+        sec: ms / 1000,
+        usec: ms % 1000 * 1000,
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut action = SignalAction::default();
+    action.handler = timer_handler as usize;
+    let old = SignalAction::default();
+    for signum in [SIGALRM, SIGVTALRM, SIGPROF] {
+        if sigaction(signum, &action, &old) < 0 {
+            panic!("Sigaction failed!");
+        }
+    }
+
+    // 取消 alarm 时返回之前剩余的秒数
+    assert_eq!(alarm(5), 0);
+    let mut curr = ITimerVal::default();
+    assert_eq!(getitimer(ITIMER_REAL, &mut curr), 0);
+    assert!(curr.value.sec <= 5 && (curr.value.sec, curr.value.usec) != (0, 0));
+    assert_eq!(alarm(0), 5);
+    assert_eq!(getitimer(ITIMER_REAL, &mut curr), 0);
+    assert_eq!((curr.value.sec, curr.value.usec), (0, 0));
+
+    // ITIMER_REAL 按照真实时间计时，进程让出 CPU 时也会到期
+    alarm(1);
+    wait_for(unsafe { addr_of!(ALRM_COUNT) }, 1, true);
+    println!("sig_timer: SIGALRM delivered");
+
+    // ITIMER_VIRTUAL 只在进程执行时计时，设置了 interval 时会周期性到期
+    let periodic = ITimerVal {
+        interval: ms(20),
+        value: ms(20),
+    };
+    assert_eq!(setitimer(ITIMER_VIRTUAL, &periodic, None), 0);
+    wait_for(unsafe { addr_of!(VTALRM_COUNT) }, 3, false);
+    assert_eq!(setitimer(ITIMER_VIRTUAL, &ITimerVal::default(), None), 0);
+    assert_eq!(getitimer(ITIMER_VIRTUAL, &mut curr), 0);
+    assert_eq!((curr.value.sec, curr.value.usec), (0, 0));
+    println!("sig_timer: SIGVTALRM delivered periodically");
+
+    let oneshot = ITimerVal {
+        interval: TimeVal::default(),
+        value: ms(30),
+    };
+    assert_eq!(setitimer(ITIMER_PROF, &oneshot, None), 0);
+    wait_for(unsafe { addr_of!(PROF_COUNT) }, 1, false);
+    assert_eq!(getitimer(ITIMER_PROF, &mut curr), 0);
+    assert_eq!((curr.value.sec, curr.value.usec), (0, 0));
+    println!("sig_timer: SIGPROF delivered");
+
+    // 不存在的定时器
+    assert_eq!(getitimer(3, &mut curr), -1);
+    // usec 超出范围，或者换算成微秒后溢出
+    let bad_usec = ITimerVal {
+        interval: TimeVal::default(),
+        value: TimeVal {
+            sec: 0,
+            usec: 1_000_000,
+        },
+    };
+    assert_eq!(setitimer(ITIMER_REAL, &bad_usec, None), -EINVAL);
+    let overflow = ITimerVal {
+        interval: TimeVal::default(),
+        value: TimeVal {
+            sec: usize::MAX / 1000,
+            usec: 0,
+        },
+    };
+    assert_eq!(setitimer(ITIMER_REAL, &overflow, None), -EINVAL);
+    assert_eq!(setitimer(ITIMER_PROF, &overflow, None), -EINVAL);
+    println!("sig_timer: invalid timers are rejected");
+    println!("sig_timer passed!");
+    0
+}
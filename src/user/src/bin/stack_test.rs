@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+// 每一层递归在栈上占用 1 KiB
+fn recurse(depth: usize) -> usize {
+    let mut buf = [0u8; 1024];
+    unsafe { core::ptr::write_volatile(&mut buf[0], depth as u8) };
+    if depth == 0 {
+        return 0;
+    }
+    let sum = recurse(depth - 1);
+    sum + unsafe { core::ptr::read_volatile(&buf[0]) } as usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 远超初始 8 KiB 用户栈的递归，栈自动向下增长
+    let depth = 1024;
+    let expected: usize = (1..=depth).map(|i| i % 256).sum();
+    assert_eq!(recurse(depth), expected);
+    println!("stack_test: the stack grows on demand");
+
+    // 子进程中的栈也可以继续增长
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(recurse(2 * depth), (1..=2 * depth).map(|i| i % 256).sum());
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, 0);
+
+    // 无限递归超过栈的大小上限后收到 SIGSEGV
+    let pid = fork();
+    if pid == 0 {
+        recurse(usize::MAX);
+        exit(0);
+    }
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, -SIGSEGV);
+    println!("stack_test passed!");
+    0
+}
@@ -0,0 +1,77 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+const PAGE_SIZE: usize = 4096;
+// 比物理内存更大的匿名映射，必须换出一部分页才能全部访问
+const SIZE: usize = 8 << 20;
+
+fn page(addr: usize, i: usize) -> *mut usize {
+    (addr + i * PAGE_SIZE) as *mut usize
+}
+
+// 每一页的开头和结尾都写入页的编号
+fn check(addr: usize) {
+    for i in 0..SIZE / PAGE_SIZE {
+        unsafe {
+            assert_eq!(page(addr, i).read_volatile(), i);
+            assert_eq!(page(addr, i + 1).sub(1).read_volatile(), !i);
+        }
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let addr = mmap(0, SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS);
+    assert!(addr > 0);
+    let addr = addr as usize;
+    for i in 0..SIZE / PAGE_SIZE {
+        unsafe {
+            page(addr, i).write_volatile(i);
+            page(addr, i + 1).sub(1).write_volatile(!i);
+        }
+    }
+    check(addr);
+    println!("swap_test: pages are swapped out and back in");
+
+    // 内核读写已经被换出的用户缓冲区
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let first = unsafe { core::slice::from_raw_parts(addr as *const u8, 8) };
+    assert_eq!(write(fds[1], first), 8);
+    check(addr);
+    let last = unsafe { core::slice::from_raw_parts_mut((addr + SIZE - 8) as *mut u8, 8) };
+    assert_eq!(read(fds[0], last), 8);
+    assert_eq!(
+        unsafe { page(addr, SIZE / PAGE_SIZE).sub(1).read_volatile() },
+        0
+    );
+    unsafe {
+        page(addr, SIZE / PAGE_SIZE)
+            .sub(1)
+            .write_volatile(!(SIZE / PAGE_SIZE - 1))
+    };
+    close(fds[0]);
+    close(fds[1]);
+    println!("swap_test: the kernel accesses swapped-out buffers");
+
+    // 子进程得到一份独立的拷贝，包括已经被换出的页
+    let pid = fork();
+    if pid == 0 {
+        check(addr);
+        unsafe { page(addr, 0).write_volatile(usize::MAX) };
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, 0);
+    check(addr);
+
+    assert_eq!(munmap(addr, SIZE), 0);
+    println!("swap_test passed!");
+    0
+}
@@ -15,7 +15,11 @@ const BS: u8 = 0x08u8;
 use alloc::string::String;
 use alloc::vec::Vec;
 use user_lib::console::getchar;
-use user_lib::{close, dup, exec, fork, open, pipe, waitpid, OpenFlags};
+use user_lib::{
+    close, dup, exec, fork, getpid, kill, open, pipe, setpgid, sigaction, tcsetpgrp,
+    waitpid_options, wifstopped, wstopsig, OpenFlags, SignalAction, SIGCONT, SIGINT, SIGQUIT,
+    SIGTSTP, SIG_DFL, SIG_IGN, WUNTRACED,
+};
 
 struct ProcessArguments {
     // input 重定向地址
@@ -70,9 +74,58 @@ impl ProcessArguments {
     }
 }
 
+// 设置终端产生的信号（Ctrl-C、Ctrl-\、Ctrl-Z）的处理方式
+fn set_job_control_signals(handler: usize) {
+    let mut action = SignalAction::default();
+    action.handler = handler;
+    for signum in [SIGINT, SIGQUIT, SIGTSTP] {
+        sigaction(signum, &action, core::ptr::null());
+    }
+}
+
+// 被 Ctrl-Z 暂停的命令，fg 把最近暂停的命令恢复到前台继续执行
+struct Job {
+    pgid: usize,
+    // 已经暂停、还没有结束的进程
+    pids: Vec<usize>,
+}
+
+// 把进程组 pgid 放到前台并等待 pids 全部结束或者暂停，有进程暂停时记录到 jobs 中
+fn wait_foreground(pgid: usize, pids: Vec<usize>, shell_pgid: usize, jobs: &mut Vec<Job>) {
+    tcsetpgrp(0, pgid);
+    let mut stopped = Vec::new();
+    let mut exit_code = 0i32;
+    for pid in pids.into_iter() {
+        let exit_pid = waitpid_options(pid, &mut exit_code, WUNTRACED);
+        assert_eq!(exit_pid, pid as isize);
+        if wifstopped(exit_code) {
+            println!(
+                "[user_shell] Process {} stopped by signal {}",
+                pid,
+                wstopsig(exit_code)
+            );
+            stopped.push(pid);
+        }
+    }
+    if !stopped.is_empty() {
+        jobs.push(Job {
+            pgid,
+            pids: stopped,
+        });
+    }
+    tcsetpgrp(0, shell_pgid);
+}
+
 #[no_mangle]
 pub fn main() -> i32 {
     println!("[user_shell] Hello, welcome to the user shell!");
+    // shell 自成一个进程组并占据前台，同时忽略终端产生的信号，
+    // 这样 Ctrl-C 只会结束正在执行的命令而不会结束 shell
+    let shell_pgid = getpid() as usize;
+    setpgid(0, 0);
+    tcsetpgrp(0, shell_pgid);
+    set_job_control_signals(SIG_IGN);
+    let mut jobs: Vec<Job> = Vec::new();
     let mut line: String = String::new();
     print!(">> ");
     loop {
@@ -80,7 +133,19 @@ pub fn main() -> i32 {
         match c {
             LF | CR => {
                 println!("");
-                if !line.is_empty() {
+                if line.trim() == "fg" {
+                    // 内置命令 fg：发送 SIGCONT 恢复最近暂停的命令，并在前台等待它
+                    match jobs.pop() {
+                        Some(job) => {
+                            for pid in job.pids.iter() {
+                                kill(*pid, SIGCONT);
+                            }
+                            wait_foreground(job.pgid, job.pids, shell_pgid, &mut jobs);
+                        }
+                        None => println!("[user_shell] fg: no stopped job"),
+                    }
+                    line.clear();
+                } else if !line.is_empty() {
                     let split: Vec<_> = line.as_str().split('|').collect();
                     let commands: Vec<_> = split
                         .iter()
@@ -116,12 +181,17 @@ pub fn main() -> i32 {
                             }
                         }
 
-                        // 使用 fork 启动子进程
+                        // 使用 fork 启动子进程，同一条命令的全部子进程属于同一个
+                        // 进程组，进程组编号为第一个子进程的 pid
                         let mut children = Vec::new();
+                        let mut pgid = 0usize;
                         for (i, cmd) in commands.iter().enumerate() {
                             let pid = fork();
                             if pid == 0 {
                                 // === child process ===
+                                setpgid(0, pgid);
+                                // exec 会保留被忽略的信号，所以需要恢复默认动作
+                                set_job_control_signals(SIG_DFL);
                                 // redirect input
                                 if !cmd.input.is_empty() {
                                     let fd = open(cmd.input.as_str(), OpenFlags::READ_ONLY);
@@ -183,6 +253,12 @@ pub fn main() -> i32 {
                                 unreachable!()
                             } else {
                                 // === parent process ===
+                                // parent 也设置一次，避免在子进程设置之前就把
+                                // 进程组交给终端
+                                if pgid == 0 {
+                                    pgid = pid as usize;
+                                }
+                                setpgid(pid as usize, pgid);
                                 children.push(pid);
                             }
                         }
@@ -196,11 +272,9 @@ pub fn main() -> i32 {
                             close(pipe_fd[1]);
                         }
 
-                        let mut exit_code = 0i32;
-                        for pid in children.into_iter() {
-                            let exit_pid = waitpid(pid as usize, &mut exit_code);
-                            assert_eq!(exit_pid, pid);
-                        }
+                        // 命令执行期间由它占据前台，Ctrl-Z 暂停的命令可以用 fg 恢复
+                        let children = children.into_iter().map(|pid| pid as usize).collect();
+                        wait_foreground(pgid, children, shell_pgid, &mut jobs);
                     }
                     line.clear();
                 }
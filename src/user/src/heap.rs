@@ -0,0 +1,47 @@
+use buddy_system_allocator::LockedHeap;
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    ptr::{null_mut, NonNull},
+};
+
+use crate::sbrk;
+
+// 堆空间不足时每次至少通过 sbrk 扩展的大小
+const HEAP_GROW_SIZE: usize = 16384;
+
+/// 用户程序的堆。初始时使用一段静态的空间，不够用时通过 sbrk 增长
+/// program break，再把新得到的内存加入到 buddy 分配器中。
+pub struct GrowableHeap(LockedHeap);
+
+impl GrowableHeap {
+    pub const fn empty() -> Self {
+        Self(LockedHeap::empty())
+    }
+
+    pub unsafe fn init(&self, start: usize, size: usize) {
+        self.0.lock().init(start, size);
+    }
+}
+
+unsafe impl GlobalAlloc for GrowableHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut heap = self.0.lock();
+        if let Ok(ptr) = heap.alloc(layout) {
+            return ptr.as_ptr();
+        }
+        // buddy 分配器分配的块按照块的大小对齐，扩展两倍的大小才能保证
+        // 新的空间中一定有一个满足要求的块
+        let size = layout.size().max(layout.align()).next_power_of_two();
+        let grow = (size * 2).max(HEAP_GROW_SIZE);
+        let start = sbrk(grow as isize);
+        if start < 0 {
+            return null_mut();
+        }
+        heap.add_to_heap(start as usize, start as usize + grow);
+        heap.alloc(layout).map_or(null_mut(), |ptr| ptr.as_ptr())
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.lock().dealloc(NonNull::new_unchecked(ptr), layout);
+    }
+}
@@ -9,6 +9,7 @@ extern crate alloc;
 // ===== mod section =====
 #[macro_use]
 pub mod console;
+mod heap;
 mod lang_items;
 use bitflags::*;
 mod syscall;
@@ -16,20 +17,77 @@ pub mod syscall_signal;
 
 // ===== use section =====
 use alloc::vec::Vec;
-use buddy_system_allocator::LockedHeap;
+use heap::GrowableHeap;
 use syscall::*;
 pub use syscall_signal::*;
 
 // ===== static section =====
 #[global_allocator]
-static HEAP: LockedHeap = LockedHeap::empty();
+static HEAP: GrowableHeap = GrowableHeap::empty();
 static mut HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
+// 内核放在用户栈上的辅助向量，0 表示没有
+static mut AUXV_BASE: usize = 0;
 
 // ===== const section =====
 const USER_HEAP_SIZE: usize = 16384;
 const WAITPID_ANY_PID: isize = -1;
 pub const WAITPID_NO_CHILDREN_RUNNING: isize = -1;
 pub const WAITPID_CHILDREN_RUNNING: isize = -2;
+// waitpid 的 options
+pub const WUNTRACED: usize = 2;
+pub const WCONTINUED: usize = 8;
+// mmap 的 prot 和 flags
+pub const PROT_READ: usize = 0x1;
+pub const PROT_WRITE: usize = 0x2;
+pub const PROT_EXEC: usize = 0x4;
+pub const MAP_SHARED: usize = 0x01;
+pub const MAP_PRIVATE: usize = 0x02;
+pub const MAP_FIXED: usize = 0x10;
+pub const MAP_ANONYMOUS: usize = 0x20;
+// msync 的 flags
+pub const MS_ASYNC: usize = 0x1;
+pub const MS_SYNC: usize = 0x4;
+// 共享内存的 key 和 flags
+pub const IPC_PRIVATE: usize = 0;
+pub const IPC_CREAT: usize = 0o1000;
+pub const IPC_EXCL: usize = 0o2000;
+pub const IPC_RMID: usize = 0;
+pub const SHM_RDONLY: usize = 0o10000;
+// personality 标志：exec 时不对地址空间布局进行随机化
+pub const ADDR_NO_RANDOMIZE: usize = 0x0040000;
+// personality 的参数为该值时只查询不修改
+pub const PERSONALITY_QUERY: usize = 0xffff_ffff;
+// ioctl 命令：查询和设置终端的前台进程组
+const TIOCGPGRP: usize = 0x540f;
+const TIOCSPGRP: usize = 0x5410;
+// 前台进程组不存在时 tcsetpgrp 返回 -ESRCH
+pub const ESRCH: isize = 3;
+// 系统调用被信号打断时返回 -EINTR
+pub const EINTR: isize = 4;
+// mmap 的 fd 不合法时返回 -EBADF
+pub const EBADF: isize = 9;
+// 排队的实时信号已经达到上限时 sigqueue 返回 -EAGAIN
+pub const EAGAIN: isize = 11;
+// 共享内存段不存在时 shmget 返回 -ENOENT，已经存在时返回 -EEXIST
+pub const ENOENT: isize = 2;
+pub const EEXIST: isize = 17;
+pub const EINVAL: isize = 22;
+// mprotect 的范围中有没有被映射的页时返回 -ENOMEM，违反 W^X 时返回 -EACCES
+pub const ENOMEM: isize = 12;
+pub const EACCES: isize = 13;
+// 辅助向量的类型
+const AT_NULL: usize = 0;
+pub const AT_PHDR: usize = 3;
+pub const AT_PHENT: usize = 4;
+pub const AT_PHNUM: usize = 5;
+pub const AT_PAGESZ: usize = 6;
+pub const AT_BASE: usize = 7;
+pub const AT_ENTRY: usize = 9;
+// 间隔定时器：按照真实时间、用户态执行时间、用户态和内核态执行时间计时，
+// 到期时分别发送 SIGALRM、SIGVTALRM、SIGPROF
+pub const ITIMER_REAL: usize = 0;
+pub const ITIMER_VIRTUAL: usize = 1;
+pub const ITIMER_PROF: usize = 2;
 
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
@@ -37,13 +95,13 @@ pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
 }
 
 // 进程在首次打开的时候会执行 _start 方法，在该方法中进一步执行了函数的主入口
-// （main），同时还兼具从 user_sp 中获取 argc 和 argv 的功能。
+// （main），同时还兼具从 user_sp 中获取 argc、argv 和辅助向量的功能。
 #[no_mangle]
 #[link_section = ".text.entry"]
-pub extern "C" fn _start(argc: usize, argv_base: usize) -> ! {
+pub extern "C" fn _start(argc: usize, argv_base: usize, auxv_base: usize) -> ! {
     unsafe {
-        HEAP.lock()
-            .init(HEAP_SPACE.as_ptr() as usize, USER_HEAP_SIZE);
+        HEAP.init(HEAP_SPACE.as_ptr() as usize, USER_HEAP_SIZE);
+        AUXV_BASE = auxv_base;
     }
     let mut argv = Vec::new();
     for i in 0..argc {
@@ -80,6 +138,21 @@ bitflags! {
     }
 }
 
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TimeVal {
+    pub sec: usize,
+    pub usec: usize,
+}
+
+// value 为下一次到期前剩余的时间，interval 为到期后重新装载的时间
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ITimerVal {
+    pub interval: TimeVal,
+    pub value: TimeVal,
+}
+
 pub fn write(fd: usize, buf: &[u8]) -> isize {
     sys_write(fd, buf)
 }
@@ -111,7 +184,7 @@ pub fn exec(path: &str, args: &[*const u8]) -> isize {
 // wait for all children to exit
 pub fn wait(exit_code: &mut i32) -> isize {
     loop {
-        match sys_waitpid(WAITPID_ANY_PID, exit_code as *mut i32) {
+        match sys_waitpid(WAITPID_ANY_PID, exit_code as *mut i32, 0) {
             WAITPID_CHILDREN_RUNNING => {
                 yield_();
             }
@@ -123,8 +196,13 @@ pub fn wait(exit_code: &mut i32) -> isize {
 
 // wait for a specific child to exit
 pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
+    waitpid_options(pid, exit_code, 0)
+}
+
+// 与 waitpid 相同，options 包含 WUNTRACED/WCONTINUED 时子进程被暂停/恢复也会返回
+pub fn waitpid_options(pid: usize, exit_code: &mut i32, options: usize) -> isize {
     loop {
-        match sys_waitpid(pid as isize, exit_code as *mut _) {
+        match sys_waitpid(pid as isize, exit_code as *mut _, options) {
             WAITPID_CHILDREN_RUNNING => {
                 yield_();
             }
@@ -134,6 +212,20 @@ pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
     }
 }
 
+// 子进程是否被暂停，此时 exit_code 中包含暂停它的信号
+pub fn wifstopped(exit_code: i32) -> bool {
+    exit_code & 0xff == 0x7f
+}
+
+pub fn wstopsig(exit_code: i32) -> i32 {
+    (exit_code >> 8) & 0xff
+}
+
+// 子进程是否被 SIGCONT 恢复
+pub fn wifcontinued(exit_code: i32) -> bool {
+    exit_code == 0xffff
+}
+
 pub fn sleep(duration: usize) {
     let start = get_time();
     while get_time() - start < duration as isize {
@@ -161,6 +253,55 @@ pub fn dup(fd: usize) -> isize {
     sys_dup(fd)
 }
 
+// 把进程 pid 加入进程组 pgid，pid 为 0 表示当前进程，pgid 为 0 表示以 pid
+// 为编号创建新的进程组
+pub fn setpgid(pid: usize, pgid: usize) -> isize {
+    sys_setpgid(pid, pgid)
+}
+
+pub fn getpgid(pid: usize) -> isize {
+    sys_getpgid(pid)
+}
+
+// 查询内核在 exec 时传入的辅助向量中类型为 aux_type 的值
+pub fn getauxval(aux_type: usize) -> Option<usize> {
+    let mut aux = unsafe { AUXV_BASE } as *const [usize; 2];
+    if aux.is_null() {
+        return None;
+    }
+    loop {
+        let [key, value] = unsafe { aux.read() };
+        match key {
+            AT_NULL => return None,
+            key if key == aux_type => return Some(value),
+            _ => aux = unsafe { aux.add(1) },
+        }
+    }
+}
+
+// 设置进程的 personality，返回原来的值
+pub fn personality(persona: usize) -> isize {
+    sys_personality(persona)
+}
+
+// 查询终端的前台进程组
+pub fn tcgetpgrp(fd: usize) -> isize {
+    let mut pgid = 0i32;
+    let ret = sys_ioctl(fd, TIOCGPGRP, &mut pgid as *mut i32 as usize);
+    if ret < 0 {
+        ret
+    } else {
+        pgid as isize
+    }
+}
+
+// 设置终端的前台进程组，终端输入的 Ctrl-C、Ctrl-\ 和 Ctrl-Z 会分别给该进程组
+// 发送 SIGINT、SIGQUIT 和 SIGTSTP
+pub fn tcsetpgrp(fd: usize, pgid: usize) -> isize {
+    let pgid = pgid as i32;
+    sys_ioctl(fd, TIOCSPGRP, &pgid as *const i32 as usize)
+}
+
 pub fn kill(pid: usize, signal: i32) -> isize {
     sys_kill(pid, signal)
 }
@@ -173,10 +314,160 @@ pub fn sigaction(
     sys_sigaction(signum, action, old_action)
 }
 
-pub fn sigprocmask(mask: u32) -> isize {
-    sys_sigprocmask(mask)
+// 按照 how 修改信号掩码，set 为 None 时只查询，oldset 返回原来的掩码。
+// SIGKILL 和 SIGSTOP 不能被屏蔽。
+pub fn sigprocmask(
+    how: usize,
+    set: Option<SignalFlags>,
+    oldset: Option<&mut SignalFlags>,
+) -> isize {
+    let set_bits = set.map(|set| set.bits());
+    let set_ptr = match set_bits.as_ref() {
+        Some(bits) => bits as *const u64,
+        None => core::ptr::null(),
+    };
+    let mut old_bits = 0u64;
+    let ret = sys_sigprocmask(how, set_ptr, &mut old_bits as *mut u64);
+    if ret == 0 {
+        if let Some(oldset) = oldset {
+            *oldset = SignalFlags::from_bits_truncate(old_bits);
+        }
+    }
+    ret
+}
+
+// 查询因为被屏蔽而处于未决状态的信号
+pub fn sigpending(set: &mut SignalFlags) -> isize {
+    let mut bits = 0u64;
+    let ret = sys_sigpending(&mut bits as *mut u64);
+    *set = SignalFlags::from_bits_truncate(bits);
+    ret
+}
+
+// 临时把信号掩码替换为 mask 并等待一个信号被递送，返回时恢复原来的掩码。
+// 总是返回 -EINTR。
+pub fn sigsuspend(mask: SignalFlags) -> isize {
+    sys_sigsuspend(&mask.bits() as *const u64)
+}
+
+// 发送一个附带数据的信号，实时信号不会被合并，而是按照发送顺序排队
+pub fn sigqueue(pid: usize, signal: i32, value: usize) -> isize {
+    sys_sigqueue(pid, signal, value)
 }
 
 pub fn sigreturn() -> isize {
     sys_sigreturn()
 }
+
+pub fn sigaltstack(ss: *const SignalStack, old_ss: *mut SignalStack) -> isize {
+    sys_sigaltstack(ss, old_ss)
+}
+
+pub fn getitimer(which: usize, curr_value: &mut ITimerVal) -> isize {
+    sys_getitimer(which, curr_value as *mut _)
+}
+
+pub fn setitimer(which: usize, new_value: &ITimerVal, old_value: Option<&mut ITimerVal>) -> isize {
+    let old_ptr = match old_value {
+        Some(old) => old as *mut _,
+        None => core::ptr::null_mut(),
+    };
+    sys_setitimer(which, new_value as *const _, old_ptr)
+}
+
+// seconds 秒后给自己发送 SIGALRM，为 0 时取消之前的 alarm。
+// 返回之前的 alarm 剩余的秒数，没有则返回 0。
+pub fn alarm(seconds: usize) -> usize {
+    let new = ITimerVal {
+        interval: TimeVal::default(),
+        value: TimeVal {
+            sec: seconds,
+            usec: 0,
+        },
+    };
+    let mut old = ITimerVal::default();
+    if setitimer(ITIMER_REAL, &new, Some(&mut old)) < 0 {
+        return 0;
+    }
+    // 与 Linux 一样四舍五入，但是剩余时间不足 1 秒时依然返回 1
+    let remaining = old.value.sec + (old.value.usec >= 500_000) as usize;
+    if remaining == 0 && old.value.usec != 0 {
+        1
+    } else {
+        remaining
+    }
+}
+
+// 把 program break 设置为 addr，成功时返回 0，失败时返回 -1
+pub fn brk(addr: usize) -> isize {
+    if sys_brk(addr) == addr as isize {
+        0
+    } else {
+        -1
+    }
+}
+
+// 把 program break 增加 increment（可以为负数），返回原来的 program break，
+// 失败时返回 -1。用户程序的堆在空间不足时也通过它来增长。
+pub fn sbrk(increment: isize) -> isize {
+    let old = sys_brk(0);
+    if increment == 0 {
+        return old;
+    }
+    match old.checked_add(increment) {
+        Some(new) if new > 0 && sys_brk(new as usize) == new => old,
+        _ => -1,
+    }
+}
+
+// 映射一段匿名内存，返回映射的起始地址，失败时返回 -errno。
+// 目前只支持 MAP_PRIVATE | MAP_ANONYMOUS，可以再加上 MAP_FIXED。
+pub fn mmap(addr: usize, len: usize, prot: usize, flags: usize) -> isize {
+    sys_mmap(addr, len, prot, flags, usize::MAX, 0)
+}
+
+// 把文件 fd 从 offset 开始的内容映射到内存中，offset 需要按页对齐。
+// MAP_SHARED 的修改会在 msync、munmap 和进程退出时写回文件。
+pub fn mmap_file(
+    addr: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    sys_mmap(addr, len, prot, flags, fd, offset)
+}
+
+// 修改 [addr, addr + len) 的权限，失败时返回 -errno。
+// 内核不允许同时可写和可执行的映射 (W^X)，违反时返回 -EACCES。
+pub fn mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    sys_mprotect(addr, len, prot)
+}
+
+pub fn msync(addr: usize, len: usize, flags: usize) -> isize {
+    sys_msync(addr, len, flags)
+}
+
+pub fn munmap(addr: usize, len: usize) -> isize {
+    sys_munmap(addr, len)
+}
+
+// 返回 key 对应的共享内存段的 id，失败时返回 -errno。
+// flags 可以是 IPC_CREAT 和 IPC_EXCL 的组合。
+pub fn shmget(key: usize, size: usize, flags: usize) -> isize {
+    sys_shmget(key, size, flags)
+}
+
+// 把共享内存段映射到 addr（为 0 时由内核选择），返回映射的起始地址
+pub fn shmat(id: usize, addr: usize, flags: usize) -> isize {
+    sys_shmat(id, addr, flags)
+}
+
+pub fn shmdt(addr: usize) -> isize {
+    sys_shmdt(addr)
+}
+
+pub fn shmctl(id: usize, cmd: usize) -> isize {
+    sys_shmctl(id, cmd, 0)
+}
@@ -1,65 +1,150 @@
 use bitflags::*;
 
-pub const MAX_SIG: usize = 31;
+pub const MAX_SIG: usize = 64;
+
+/// 特殊的信号处理函数：执行默认动作
+pub const SIG_DFL: usize = 0;
+/// 特殊的信号处理函数：忽略信号
+pub const SIG_IGN: usize = 1;
+
+/// sigprocmask: 在当前掩码中加入 set
+pub const SIG_BLOCK: usize = 0;
+/// sigprocmask: 从当前掩码中去掉 set
+pub const SIG_UNBLOCK: usize = 1;
+/// sigprocmask: 把当前掩码替换为 set
+pub const SIG_SETMASK: usize = 2;
+
+// 取值与 Linux 保持一致
+bitflags! {
+    pub struct SignalActionFlags: u32 {
+        // 信号处理函数额外接收 SignalInfo 和信号帧的地址
+        const SA_SIGINFO = 0x4;
+        // 在 sigaltstack 设置的备用信号栈上执行信号处理函数
+        const SA_ONSTACK = 0x0800_0000;
+        // 被信号打断的阻塞系统调用在信号处理函数返回后自动重新执行
+        const SA_RESTART = 0x1000_0000;
+        // 执行信号处理函数时不屏蔽当前信号
+        const SA_NODEFER = 0x4000_0000;
+        // 信号递送后恢复为 SIG_DFL
+        const SA_RESETHAND = 0x8000_0000;
+    }
+}
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct SignalAction {
-    // 信号处理函数
+    // 信号处理函数，也可以是 SIG_DFL 或者 SIG_IGN。
+    // 信号处理函数的原型为 fn(signum: usize)，设置了 SA_SIGINFO 时为
+    // fn(signum: usize, info: &SignalInfo, frame: usize)，
+    // 处理函数可以直接返回，也可以主动调用 sigreturn。
     pub handler: usize,
-    // 信号掩码
+    // 执行信号处理函数期间额外屏蔽的信号
     pub mask: SignalFlags,
+    pub flags: SignalActionFlags,
 }
 
 impl Default for SignalAction {
     fn default() -> Self {
         Self {
-            handler: 0,
-            // 40 -> 0b00101000: 屏蔽 SIGILL 和 SIGABRT
-            // TODO(justxuewei): 为啥要默认屏蔽这两个信号？
-            mask: SignalFlags::from_bits(40).unwrap(),
+            handler: SIG_DFL,
+            mask: SignalFlags::empty(),
+            flags: SignalActionFlags::empty(),
+        }
+    }
+}
+
+/// ss_flags: 当前正在备用信号栈上执行
+pub const SS_ONSTACK: i32 = 1;
+/// ss_flags: 备用信号栈被禁用
+pub const SS_DISABLE: i32 = 2;
+/// 备用信号栈的最小长度
+pub const MINSIGSTKSZ: usize = 2048;
+/// 备用信号栈的推荐长度
+pub const SIGSTKSZ: usize = 8192;
+
+/// 备用信号栈（stack_t）
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SignalStack {
+    pub sp: usize,
+    pub flags: i32,
+    pub size: usize,
+}
+
+impl Default for SignalStack {
+    fn default() -> Self {
+        Self {
+            sp: 0,
+            flags: SS_DISABLE,
+            size: 0,
         }
     }
 }
 
+/// si_code: 信号由 kill 等系统调用发送
+pub const SI_USER: i32 = 0;
+/// si_code: 信号由内核产生，比如缺页异常
+pub const SI_KERNEL: i32 = 0x80;
+/// si_code: 信号由 sigqueue 发送
+pub const SI_QUEUE: i32 = -1;
+/// SIGCHLD 的 si_code: 子进程被暂停
+pub const CLD_STOPPED: i32 = 5;
+/// SIGCHLD 的 si_code: 子进程被恢复
+pub const CLD_CONTINUED: i32 = 6;
+
+/// SA_SIGINFO 信号处理函数接收到的信号信息
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SignalInfo {
+    pub signo: i32,
+    pub code: i32,
+    // 发送信号的进程 pid
+    pub pid: usize,
+    // 导致异常的地址（SIGSEGV/SIGILL 等）
+    pub addr: usize,
+    // sigqueue 附带的数据，可以是整数也可以是指针
+    pub value: usize,
+}
+
+// 编号为 signum 的信号对应第 signum - 1 位
 bitflags! {
-    pub struct SignalFlags: u32 {
-        const SIGDEF = 1; // Default signal handling
-        const SIGHUP = 1 << 1;
-        const SIGINT = 1 << 2;
-        const SIGQUIT = 1 << 3;
-        const SIGILL = 1 << 4; // 指令异常
-        const SIGTRAP = 1 << 5;
-        const SIGABRT = 1 << 6;
-        const SIGBUS = 1 << 7;
-        const SIGFPE = 1 << 8;
-        const SIGKILL = 1 << 9; // 结束进程
-        const SIGUSR1 = 1 << 10;
-        const SIGSEGV = 1 << 11; // 内存段异常
-        const SIGUSR2 = 1 << 12;
-        const SIGPIPE = 1 << 13;
-        const SIGALRM = 1 << 14;
-        const SIGTERM = 1 << 15;
-        const SIGSTKFLT = 1 << 16;
-        const SIGCHLD = 1 << 17;
-        const SIGCONT = 1 << 18; // 恢复进程
-        const SIGSTOP = 1 << 19; // 暂停进程
-        const SIGTSTP = 1 << 20;
-        const SIGTTIN = 1 << 21;
-        const SIGTTOU = 1 << 22;
-        const SIGURG = 1 << 23;
-        const SIGXCPU = 1 << 24;
-        const SIGXFSZ = 1 << 25;
-        const SIGVTALRM = 1 << 26;
-        const SIGPROF = 1 << 27;
-        const SIGWINCH = 1 << 28;
-        const SIGIO = 1 << 29;
-        const SIGPWR = 1 << 30;
-        const SIGSYS = 1 << 31;
+    pub struct SignalFlags: u64 {
+        const SIGHUP = 1 << 0;
+        const SIGINT = 1 << 1;
+        const SIGQUIT = 1 << 2;
+        const SIGILL = 1 << 3; // 指令异常
+        const SIGTRAP = 1 << 4;
+        const SIGABRT = 1 << 5;
+        const SIGBUS = 1 << 6;
+        const SIGFPE = 1 << 7;
+        const SIGKILL = 1 << 8; // 结束进程
+        const SIGUSR1 = 1 << 9;
+        const SIGSEGV = 1 << 10; // 内存段异常
+        const SIGUSR2 = 1 << 11;
+        const SIGPIPE = 1 << 12;
+        const SIGALRM = 1 << 13;
+        const SIGTERM = 1 << 14;
+        const SIGSTKFLT = 1 << 15;
+        const SIGCHLD = 1 << 16;
+        const SIGCONT = 1 << 17; // 恢复进程
+        const SIGSTOP = 1 << 18; // 暂停进程
+        const SIGTSTP = 1 << 19;
+        const SIGTTIN = 1 << 20;
+        const SIGTTOU = 1 << 21;
+        const SIGURG = 1 << 22;
+        const SIGXCPU = 1 << 23;
+        const SIGXFSZ = 1 << 24;
+        const SIGVTALRM = 1 << 25;
+        const SIGPROF = 1 << 26;
+        const SIGWINCH = 1 << 27;
+        const SIGIO = 1 << 28;
+        const SIGPWR = 1 << 29;
+        const SIGSYS = 1 << 30;
+        // 全部实时信号 SIGRTMIN..=SIGRTMAX
+        const SIGRT = 0xffff_ffff_8000_0000;
     }
 }
 
-pub const SIGDEF: i32 = 0; // Default signal handling
 pub const SIGHUP: i32 = 1;
 pub const SIGINT: i32 = 2;
 pub const SIGQUIT: i32 = 3;
@@ -91,3 +176,6 @@ pub const SIGWINCH: i32 = 28;
 pub const SIGIO: i32 = 29;
 pub const SIGPWR: i32 = 30;
 pub const SIGSYS: i32 = 31;
+// 实时信号会排队递送，可以通过 sigqueue 附带数据
+pub const SIGRTMIN: i32 = 32;
+pub const SIGRTMAX: i32 = 64;